@@ -0,0 +1,53 @@
+// Tokenizes generated scripts of doubling size. Scanning is linear when the
+// time per megabyte stays flat as the input grows.
+//
+//     cargo bench --bench scanner
+use codecrafters_interpreter::scanner::Scanner;
+use std::time::{Duration, Instant};
+
+const SNIPPET: &str = r#"// Compute a few values, with a comment in Ünïcode.
+fun fib(n) {
+  if (n < 2) return n;
+  return fib(n - 1) + fib(n - 2);
+}
+var greeting = "héllo, wörld 日本語";
+for (var i = 0; i < 10; i = i + 1) {
+  print greeting + " " + "x";
+  print fib(i) * 3.14159 >= 2 and !false;
+}
+"#;
+
+const MEGABYTE: usize = 1024 * 1024;
+
+fn generate(size: usize) -> String {
+    SNIPPET.repeat(size / SNIPPET.len() + 1)
+}
+
+fn scan(source: &str) -> (Duration, usize) {
+    let started = Instant::now();
+    let (tokens, errors) = Scanner::new(source.to_string()).scan_tokens();
+    let elapsed = started.elapsed();
+
+    assert!(errors.is_empty());
+    (elapsed, tokens.len())
+}
+
+fn main() {
+    let mut baseline = None;
+
+    for megabytes in [1, 2, 4, 8, 16] {
+        let source = generate(megabytes * MEGABYTE);
+        let (elapsed, tokens) = (0..3).map(|_| scan(&source)).min().unwrap();
+
+        let per_megabyte = elapsed.as_secs_f64() * MEGABYTE as f64 / source.len() as f64;
+        let baseline = *baseline.get_or_insert(per_megabyte);
+        println!(
+            "{:>3} MiB  {:>9} tokens  {:>8.2} ms  {:>6.2} ms/MiB  {:.2}x",
+            megabytes,
+            tokens,
+            elapsed.as_secs_f64() * 1000.0,
+            per_megabyte * 1000.0,
+            per_megabyte / baseline
+        );
+    }
+}
@@ -1,6 +1,4 @@
-use crate::expr::{Assign, Binary, Expr, Grouping, Literal, Unary, Variable, Visitor};
-use std::ptr::null;
-use crate::token_type::LiteralValue;
+use crate::expr::{Assign, Binary, Call, Expr, Get, GetIndex, Grouping, Interpolation, List, Literal, Logical, Map, Set, SetIndex, Super, This, Unary, Variable, Visitor};
 
 pub struct AstPrinter {}
 
@@ -9,24 +7,24 @@ impl AstPrinter {
         expr.accept(self)
     }
 
-    fn parenthesize(&mut self, name: String, exprs: &[&Box<Expr>]) -> String {
+    fn parenthesize(&mut self, name: String, exprs: &[&Expr]) -> String {
 
         let mut final_string = String::new();
 
-        final_string.push_str("(");
+        final_string.push('(');
         final_string.push_str(&name);
         for expr in exprs {
-            final_string.push_str(" ");
+            final_string.push(' ');
             final_string.push_str(&expr.accept(self));
         }
-        final_string.push_str(")");
+        final_string.push(')');
         final_string
     }
 }
 
 impl Visitor<String> for AstPrinter {
     fn visit_assign_expr(&mut self, expr: &Assign) -> String {
-        todo!()
+        self.parenthesize(format!("= {}", expr.name.lexeme), &[&expr.value])
     }
 
     fn visit_binary_expr(&mut self, expr: &Binary) -> String {
@@ -34,14 +32,63 @@ impl Visitor<String> for AstPrinter {
         self.parenthesize(expr.operator.lexeme.to_string(), &[&expr.left, &expr.right])
     }
 
+    fn visit_map_expr(&mut self, expr: &Map) -> String {
+        let entries: Vec<&Expr> = expr.entries.iter().flat_map(|(key, value)| [key, value]).collect();
+        self.parenthesize("map".to_string(), &entries)
+    }
+
+    fn visit_call_expr(&mut self, expr: &Call) -> String {
+        let mut exprs = vec![expr.callee.as_ref()];
+        exprs.extend(expr.arguments.iter());
+        self.parenthesize("call".to_string(), &exprs)
+    }
+
+    fn visit_get_expr(&mut self, expr: &Get) -> String {
+        self.parenthesize(format!(". {}", expr.name.lexeme), &[&expr.object])
+    }
+
     fn visit_grouping_expr(&mut self, expr: &Grouping) -> String {
         self.parenthesize("group".to_string(), &[&expr.expression])
     }
 
+    fn visit_get_index_expr(&mut self, expr: &GetIndex) -> String {
+        self.parenthesize("[]".to_string(), &[&expr.object, &expr.index])
+    }
+
+    fn visit_interpolation_expr(&mut self, expr: &Interpolation) -> String {
+        let parts: Vec<&Expr> = expr.parts.iter().collect();
+        self.parenthesize("interpolate".to_string(), &parts)
+    }
+
+    fn visit_list_expr(&mut self, expr: &List) -> String {
+        let elements: Vec<&Expr> = expr.elements.iter().collect();
+        self.parenthesize("list".to_string(), &elements)
+    }
+
     fn visit_literal_expr(&mut self, expr: &Literal) -> String {
         expr.value.to_string()
     }
 
+    fn visit_logical_expr(&mut self, expr: &Logical) -> String {
+        self.parenthesize(expr.operator.lexeme.to_string(), &[&expr.left, &expr.right])
+    }
+
+    fn visit_set_expr(&mut self, expr: &Set) -> String {
+        self.parenthesize(format!("= .{}", expr.name.lexeme), &[&expr.object, &expr.value])
+    }
+
+    fn visit_set_index_expr(&mut self, expr: &SetIndex) -> String {
+        self.parenthesize("= []".to_string(), &[&expr.object, &expr.index, &expr.value])
+    }
+
+    fn visit_super_expr(&mut self, expr: &Super) -> String {
+        format!("super.{}", expr.method.lexeme)
+    }
+
+    fn visit_this_expr(&mut self, expr: &This) -> String {
+        expr.keyword.lexeme.to_string()
+    }
+
     fn visit_unary_expr(&mut self, expr: &Unary) -> String {
         self.parenthesize(expr.operator.lexeme.to_string(), &[&expr.right])
     }
@@ -50,6 +97,6 @@ impl Visitor<String> for AstPrinter {
         // self.parenthesize("group".to_string(), &[&Box::new(Expr::Literal(Literal {
         //     value: LiteralValue::String(expr.clone().name.lexeme),
         // }))])
-        expr.clone().name.lexeme
+        expr.name.lexeme.to_string()
     }
 }
@@ -0,0 +1,433 @@
+use bytes::{Buf, BufMut};
+use crate::chunk::{Chunk, OpCode, Program, Prototype};
+use crate::error::BytecodeError;
+use crate::token_type::LiteralValue;
+
+// Layout of a compiled program, all integers little-endian:
+//
+//   magic "LOXB", format version (u16), function count (u32), then for every
+//   function: name, arity (u8), upvalue count (u16), constant pool, code and
+//   a run-length encoded line table.
+//
+// Strings are a u32 byte length followed by UTF-8, constants a tag byte
+// followed by the value.
+pub const MAGIC: &[u8; 4] = b"LOXB";
+pub const VERSION: u16 = 4;
+
+const TAG_NIL: u8 = 0;
+const TAG_FALSE: u8 = 1;
+const TAG_TRUE: u8 = 2;
+const TAG_NUMBER: u8 = 3;
+const TAG_STRING: u8 = 4;
+
+pub fn is_compiled(bytes: &[u8]) -> bool {
+    bytes.starts_with(MAGIC)
+}
+
+pub fn encode(program: &Program) -> Vec<u8> {
+    let mut buf = Vec::new();
+    buf.put_slice(MAGIC);
+    buf.put_u16_le(VERSION);
+    buf.put_u32_le(program.functions.len() as u32);
+
+    for function in &program.functions {
+        put_string(&mut buf, &function.name);
+        buf.put_u8(function.arity as u8);
+        buf.put_u16_le(function.upvalues as u16);
+
+        let chunk = &function.chunk;
+        buf.put_u32_le(chunk.constants.len() as u32);
+        for constant in &chunk.constants {
+            match constant {
+                LiteralValue::Bool(false) => buf.put_u8(TAG_FALSE),
+                LiteralValue::Bool(true) => buf.put_u8(TAG_TRUE),
+                LiteralValue::Number(number) => {
+                    buf.put_u8(TAG_NUMBER);
+                    buf.put_f64_le(*number);
+                }
+                LiteralValue::String(string) => {
+                    buf.put_u8(TAG_STRING);
+                    put_string(&mut buf, string);
+                }
+                // Runtime objects never end up in a constant pool.
+                _ => buf.put_u8(TAG_NIL),
+            }
+        }
+
+        buf.put_u32_le(chunk.code.len() as u32);
+        buf.put_slice(&chunk.code);
+
+        let runs = line_runs(&chunk.lines);
+        buf.put_u32_le(runs.len() as u32);
+        for (line, count) in runs {
+            buf.put_u32_le(line as u32);
+            buf.put_u32_le(count as u32);
+        }
+    }
+
+    buf
+}
+
+pub fn decode(bytes: &[u8]) -> Result<Program, BytecodeError> {
+    if !is_compiled(bytes) {
+        return Err(BytecodeError::BadMagic);
+    }
+
+    let mut reader = Reader { buf: &bytes[MAGIC.len()..] };
+    let version = reader.u16()?;
+    if version != VERSION {
+        return Err(BytecodeError::VersionMismatch {
+            found: version,
+            expected: VERSION,
+        });
+    }
+
+    let count = reader.u32()? as usize;
+    let mut functions = Vec::new();
+    for _ in 0..count {
+        functions.push(reader.function()?);
+    }
+
+    if reader.buf.has_remaining() {
+        return Err(BytecodeError::Corrupt(format!("{} unexpected bytes after the last function", reader.buf.remaining())));
+    }
+
+    let program = Program { functions };
+    verify(&program)?;
+    Ok(program)
+}
+
+fn put_string(buf: &mut Vec<u8>, string: &str) {
+    buf.put_u32_le(string.len() as u32);
+    buf.put_slice(string.as_bytes());
+}
+
+// Consecutive bytes nearly always share a line, so store (line, count) pairs.
+fn line_runs(lines: &[usize]) -> Vec<(usize, usize)> {
+    let mut runs: Vec<(usize, usize)> = Vec::new();
+
+    for &line in lines {
+        match runs.last_mut() {
+            Some((last, count)) if *last == line => *count += 1,
+            _ => runs.push((line, 1)),
+        }
+    }
+
+    runs
+}
+
+struct Reader<'a> {
+    buf: &'a [u8],
+}
+
+impl Reader<'_> {
+    fn need(&self, len: usize) -> Result<(), BytecodeError> {
+        if self.buf.remaining() < len {
+            Err(BytecodeError::Truncated)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn u8(&mut self) -> Result<u8, BytecodeError> {
+        self.need(1)?;
+        Ok(self.buf.get_u8())
+    }
+
+    fn u16(&mut self) -> Result<u16, BytecodeError> {
+        self.need(2)?;
+        Ok(self.buf.get_u16_le())
+    }
+
+    fn u32(&mut self) -> Result<u32, BytecodeError> {
+        self.need(4)?;
+        Ok(self.buf.get_u32_le())
+    }
+
+    fn f64(&mut self) -> Result<f64, BytecodeError> {
+        self.need(8)?;
+        Ok(self.buf.get_f64_le())
+    }
+
+    fn bytes(&mut self, len: usize) -> Result<Vec<u8>, BytecodeError> {
+        self.need(len)?;
+        let bytes = self.buf[..len].to_vec();
+        self.buf.advance(len);
+        Ok(bytes)
+    }
+
+    fn string(&mut self) -> Result<String, BytecodeError> {
+        let len = self.u32()? as usize;
+        String::from_utf8(self.bytes(len)?).map_err(|_| BytecodeError::Corrupt("a string is not valid UTF-8".to_string()))
+    }
+
+    fn function(&mut self) -> Result<Prototype, BytecodeError> {
+        let name = self.string()?;
+        let arity = self.u8()? as usize;
+        let upvalues = self.u16()? as usize;
+
+        let mut chunk = Chunk::default();
+        let constants = self.u32()?;
+        for _ in 0..constants {
+            let constant = match self.u8()? {
+                TAG_NIL => LiteralValue::Nil,
+                TAG_FALSE => LiteralValue::Bool(false),
+                TAG_TRUE => LiteralValue::Bool(true),
+                TAG_NUMBER => LiteralValue::Number(self.f64()?),
+                TAG_STRING => LiteralValue::String(self.string()?.into()),
+                tag => return Err(BytecodeError::Corrupt(format!("unknown constant tag {}", tag))),
+            };
+            chunk.constants.push(constant);
+        }
+
+        let len = self.u32()? as usize;
+        chunk.code = self.bytes(len)?;
+
+        let runs = self.u32()?;
+        for _ in 0..runs {
+            let line = self.u32()? as usize;
+            let count = self.u32()? as usize;
+            if chunk.lines.len() + count > chunk.code.len() {
+                return Err(BytecodeError::Corrupt(format!("the line table of '{}' is longer than its code", name)));
+            }
+            chunk.lines.extend(std::iter::repeat(line).take(count));
+        }
+        if chunk.lines.len() != chunk.code.len() {
+            return Err(BytecodeError::Corrupt(format!("the line table of '{}' is shorter than its code", name)));
+        }
+
+        Ok(Prototype {
+            name,
+            arity,
+            upvalues,
+            chunk,
+        })
+    }
+}
+
+// Checks that every instruction decodes and only refers to constants,
+// functions and offsets that exist, then follows every path through each
+// function to make sure no instruction pops more than is on its frame or
+// reaches past it. Upvalues are the only stack accesses left to the VM to
+// check.
+fn verify(program: &Program) -> Result<(), BytecodeError> {
+    if program.functions.is_empty() {
+        return Err(BytecodeError::Corrupt("the program has no top-level script".to_string()));
+    }
+    // The VM starts the script with nothing but its closure on the stack.
+    if program.functions[0].arity != 0 || program.functions[0].upvalues != 0 {
+        return Err(BytecodeError::Corrupt("the top-level script takes arguments or upvalues".to_string()));
+    }
+
+    for function in &program.functions {
+        let chunk = &function.chunk;
+        let corrupt = |offset: usize, problem: &str| {
+            BytecodeError::Corrupt(format!("{} at offset {} of '{}'", problem, offset, function.name))
+        };
+
+        // Where each instruction starts, jumps may only land on one.
+        let mut starts = vec![false; chunk.code.len()];
+        let mut offset = 0;
+        let mut last = None;
+        while offset < chunk.code.len() {
+            let op = OpCode::from_byte(chunk.code[offset]).ok_or_else(|| corrupt(offset, "unknown opcode"))?;
+            starts[offset] = true;
+
+            let next = offset + 1 + operand_count(op);
+            if next > chunk.code.len() {
+                return Err(corrupt(offset, "missing operands"));
+            }
+
+            match op {
+                OpCode::Constant if chunk.read_u16(offset + 1) as usize >= chunk.constants.len() => {
+                    return Err(corrupt(offset, "constant out of range"));
+                }
+                OpCode::GetGlobal
+                | OpCode::DefineGlobal
+                | OpCode::SetGlobal
+                | OpCode::GetProperty
+                | OpCode::SetProperty
+                | OpCode::GetSuper
+                | OpCode::Class
+                | OpCode::Method
+                | OpCode::Invoke => match chunk.constants.get(chunk.read_u16(offset + 1) as usize) {
+                    Some(LiteralValue::String(_)) => {}
+                    _ => return Err(corrupt(offset, "name is not a string constant")),
+                },
+                OpCode::Jump | OpCode::JumpIfFalse if next + chunk.read_u16(offset + 1) as usize >= chunk.code.len() => {
+                    return Err(corrupt(offset, "jump past the end of the code"));
+                }
+                OpCode::Loop if chunk.read_u16(offset + 1) as usize > next => {
+                    return Err(corrupt(offset, "loop before the start of the code"));
+                }
+                OpCode::GetUpvalue | OpCode::SetUpvalue if chunk.code[offset + 1] as usize >= function.upvalues => {
+                    return Err(corrupt(offset, "upvalue out of range"));
+                }
+                OpCode::Closure => {
+                    let index = chunk.read_u16(offset + 1) as usize;
+                    let upvalues = match program.functions.get(index) {
+                        Some(closure) if index > 0 => closure.upvalues,
+                        _ => return Err(corrupt(offset, "closure over an unknown function")),
+                    };
+                    if next + upvalues * 2 > chunk.code.len() {
+                        return Err(corrupt(offset, "missing upvalue operands"));
+                    }
+                    for capture in (next..next + upvalues * 2).step_by(2) {
+                        let is_local = chunk.code[capture] == 1;
+                        if !is_local && chunk.code[capture + 1] as usize >= function.upvalues {
+                            return Err(corrupt(offset, "closure captures an upvalue out of range"));
+                        }
+                    }
+                    last = Some(op);
+                    offset = next + upvalues * 2;
+                    continue;
+                }
+                _ => {}
+            }
+
+            last = Some(op);
+            offset = next;
+        }
+
+        if last != Some(OpCode::Return) {
+            return Err(BytecodeError::Corrupt(format!("'{}' does not end with a return", function.name)));
+        }
+
+        verify_stack(program, function, &starts, corrupt)?;
+    }
+
+    Ok(())
+}
+
+fn operand_count(op: OpCode) -> usize {
+    match op {
+        OpCode::GetLocal | OpCode::SetLocal | OpCode::GetUpvalue | OpCode::SetUpvalue | OpCode::Call | OpCode::Interpolate => 1,
+        OpCode::Constant
+        | OpCode::GetGlobal
+        | OpCode::DefineGlobal
+        | OpCode::SetGlobal
+        | OpCode::GetProperty
+        | OpCode::SetProperty
+        | OpCode::GetSuper
+        | OpCode::Class
+        | OpCode::Method
+        | OpCode::Jump
+        | OpCode::JumpIfFalse
+        | OpCode::Loop
+        | OpCode::Closure
+        | OpCode::BuildList
+        | OpCode::BuildMap => 2,
+        OpCode::Invoke => 3,
+        _ => 0,
+    }
+}
+
+// Works out how many values are on the frame before every reachable
+// instruction. The callee and its arguments are there from the start, and
+// paths that meet have to agree, so the depth is the same on every visit.
+fn verify_stack(
+    program: &Program,
+    function: &Prototype,
+    starts: &[bool],
+    corrupt: impl Fn(usize, &str) -> BytecodeError,
+) -> Result<(), BytecodeError> {
+    let chunk = &function.chunk;
+    let mut depths: Vec<Option<usize>> = vec![None; chunk.code.len()];
+    let mut pending = vec![(0, function.arity + 1)];
+
+    while let Some((offset, depth)) = pending.pop() {
+        if !starts[offset] {
+            return Err(corrupt(offset, "jump into the middle of an instruction"));
+        }
+        match depths[offset] {
+            Some(seen) if seen == depth => continue,
+            Some(_) => return Err(corrupt(offset, "paths reach the instruction with different stack depths")),
+            None => depths[offset] = Some(depth),
+        }
+
+        let op = OpCode::from_byte(chunk.code[offset]).expect("verified opcode");
+        let byte = |index: usize| chunk.code[offset + index] as usize;
+        let u16 = |index: usize| chunk.read_u16(offset + index) as usize;
+        let mut next = offset + 1 + operand_count(op);
+
+        let (pops, pushes) = match op {
+            OpCode::Constant
+            | OpCode::Nil
+            | OpCode::True
+            | OpCode::False
+            | OpCode::GetGlobal
+            | OpCode::GetUpvalue
+            | OpCode::Class => (0, 1),
+            OpCode::GetLocal => {
+                if byte(1) >= depth {
+                    return Err(corrupt(offset, "local slot out of range"));
+                }
+                (0, 1)
+            }
+            OpCode::SetLocal => {
+                if byte(1) >= depth {
+                    return Err(corrupt(offset, "local slot out of range"));
+                }
+                (1, 1)
+            }
+            OpCode::Closure => {
+                let upvalues = &chunk.code[next..next + program.functions[u16(1)].upvalues * 2];
+                for capture in upvalues.chunks(2) {
+                    if capture[0] == 1 && capture[1] as usize >= depth {
+                        return Err(corrupt(offset, "closure captures a local slot out of range"));
+                    }
+                }
+                next += upvalues.len();
+                (0, 1)
+            }
+            OpCode::Pop | OpCode::DefineGlobal | OpCode::Print | OpCode::CloseUpvalue => (1, 0),
+            OpCode::SetGlobal
+            | OpCode::SetUpvalue
+            | OpCode::GetProperty
+            | OpCode::Not
+            | OpCode::Negate
+            | OpCode::JumpIfFalse => (1, 1),
+            OpCode::SetProperty
+            | OpCode::GetSuper
+            | OpCode::Equal
+            | OpCode::NotEqual
+            | OpCode::Greater
+            | OpCode::GreaterEqual
+            | OpCode::Less
+            | OpCode::LessEqual
+            | OpCode::Add
+            | OpCode::Subtract
+            | OpCode::Multiply
+            | OpCode::Divide
+            | OpCode::GetIndex
+            | OpCode::Inherit
+            | OpCode::Method => (2, 1),
+            OpCode::SetIndex => (3, 1),
+            OpCode::Call => (byte(1) + 1, 1),
+            OpCode::Invoke => (byte(3) + 1, 1),
+            OpCode::Interpolate => (byte(1), 1),
+            OpCode::BuildList => (u16(1), 1),
+            OpCode::BuildMap => (u16(1) * 2, 1),
+            OpCode::Jump | OpCode::Loop => (0, 0),
+            OpCode::Return => (1, 0),
+        };
+
+        if pops > depth {
+            return Err(corrupt(offset, "stack underflow"));
+        }
+        let depth = depth - pops + pushes;
+
+        match op {
+            OpCode::Return => {}
+            OpCode::Jump => pending.push((next + u16(1), depth)),
+            OpCode::Loop => pending.push((next - u16(1), depth)),
+            OpCode::JumpIfFalse => {
+                pending.push((next + u16(1), depth));
+                pending.push((next, depth));
+            }
+            _ => pending.push((next, depth)),
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,225 @@
+use crate::token_type::LiteralValue;
+use std::fmt::{Display, Formatter};
+
+// Every instruction is a single opcode byte followed by its operands. Constant
+// and name operands are u16 indices into the pool, jumps, list lengths and map
+// sizes are u16 and local, upvalue, argument and interpolated part counts fit
+// in a byte. `Invoke` takes a name followed by an argument count.
+#[repr(u8)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum OpCode {
+    Constant,
+    Nil,
+    True,
+    False,
+    Pop,
+    GetLocal,
+    SetLocal,
+    GetGlobal,
+    DefineGlobal,
+    SetGlobal,
+    GetUpvalue,
+    SetUpvalue,
+    GetProperty,
+    SetProperty,
+    GetSuper,
+    Equal,
+    NotEqual,
+    Greater,
+    GreaterEqual,
+    Less,
+    LessEqual,
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Not,
+    Negate,
+    Print,
+    Jump,
+    JumpIfFalse,
+    Loop,
+    Call,
+    Closure,
+    CloseUpvalue,
+    Return,
+    Class,
+    Inherit,
+    Method,
+    Interpolate,
+    BuildList,
+    GetIndex,
+    SetIndex,
+    BuildMap,
+    Invoke,
+}
+
+impl OpCode {
+    // Listed in declaration order so a byte indexes straight into it.
+    const ALL: [OpCode; 44] = [
+        OpCode::Constant,
+        OpCode::Nil,
+        OpCode::True,
+        OpCode::False,
+        OpCode::Pop,
+        OpCode::GetLocal,
+        OpCode::SetLocal,
+        OpCode::GetGlobal,
+        OpCode::DefineGlobal,
+        OpCode::SetGlobal,
+        OpCode::GetUpvalue,
+        OpCode::SetUpvalue,
+        OpCode::GetProperty,
+        OpCode::SetProperty,
+        OpCode::GetSuper,
+        OpCode::Equal,
+        OpCode::NotEqual,
+        OpCode::Greater,
+        OpCode::GreaterEqual,
+        OpCode::Less,
+        OpCode::LessEqual,
+        OpCode::Add,
+        OpCode::Subtract,
+        OpCode::Multiply,
+        OpCode::Divide,
+        OpCode::Not,
+        OpCode::Negate,
+        OpCode::Print,
+        OpCode::Jump,
+        OpCode::JumpIfFalse,
+        OpCode::Loop,
+        OpCode::Call,
+        OpCode::Closure,
+        OpCode::CloseUpvalue,
+        OpCode::Return,
+        OpCode::Class,
+        OpCode::Inherit,
+        OpCode::Method,
+        OpCode::Interpolate,
+        OpCode::BuildList,
+        OpCode::GetIndex,
+        OpCode::SetIndex,
+        OpCode::BuildMap,
+        OpCode::Invoke,
+    ];
+
+    pub fn from_byte(byte: u8) -> Option<OpCode> {
+        OpCode::ALL.get(byte as usize).copied()
+    }
+}
+
+impl Display for OpCode {
+    // Padded so the disassembler can line up operands.
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.pad(
+            match self {
+                OpCode::Constant => "CONSTANT",
+                OpCode::Nil => "NIL",
+                OpCode::True => "TRUE",
+                OpCode::False => "FALSE",
+                OpCode::Pop => "POP",
+                OpCode::GetLocal => "GET_LOCAL",
+                OpCode::SetLocal => "SET_LOCAL",
+                OpCode::GetGlobal => "GET_GLOBAL",
+                OpCode::DefineGlobal => "DEFINE_GLOBAL",
+                OpCode::SetGlobal => "SET_GLOBAL",
+                OpCode::GetUpvalue => "GET_UPVALUE",
+                OpCode::SetUpvalue => "SET_UPVALUE",
+                OpCode::GetProperty => "GET_PROPERTY",
+                OpCode::SetProperty => "SET_PROPERTY",
+                OpCode::GetSuper => "GET_SUPER",
+                OpCode::Equal => "EQUAL",
+                OpCode::NotEqual => "NOT_EQUAL",
+                OpCode::Greater => "GREATER",
+                OpCode::GreaterEqual => "GREATER_EQUAL",
+                OpCode::Less => "LESS",
+                OpCode::LessEqual => "LESS_EQUAL",
+                OpCode::Add => "ADD",
+                OpCode::Subtract => "SUBTRACT",
+                OpCode::Multiply => "MULTIPLY",
+                OpCode::Divide => "DIVIDE",
+                OpCode::Not => "NOT",
+                OpCode::Negate => "NEGATE",
+                OpCode::Print => "PRINT",
+                OpCode::Jump => "JUMP",
+                OpCode::JumpIfFalse => "JUMP_IF_FALSE",
+                OpCode::Loop => "LOOP",
+                OpCode::Call => "CALL",
+                OpCode::Closure => "CLOSURE",
+                OpCode::CloseUpvalue => "CLOSE_UPVALUE",
+                OpCode::Return => "RETURN",
+                OpCode::Class => "CLASS",
+                OpCode::Inherit => "INHERIT",
+                OpCode::Method => "METHOD",
+                OpCode::Interpolate => "INTERPOLATE",
+                OpCode::BuildList => "BUILD_LIST",
+                OpCode::GetIndex => "GET_INDEX",
+                OpCode::SetIndex => "SET_INDEX",
+                OpCode::BuildMap => "BUILD_MAP",
+                OpCode::Invoke => "INVOKE",
+            },
+        )
+    }
+}
+
+// The instructions of a single function. `lines` holds the source line of
+// every byte in `code` and the constant pool only ever contains strings,
+// numbers, booleans and nil.
+#[derive(Clone, Debug, Default)]
+pub struct Chunk {
+    pub code: Vec<u8>,
+    pub lines: Vec<usize>,
+    pub constants: Vec<LiteralValue>,
+}
+
+impl Chunk {
+    pub fn write(&mut self, byte: u8, line: usize) {
+        self.code.push(byte);
+        self.lines.push(line);
+    }
+
+    pub fn write_u16(&mut self, value: u16, line: usize) {
+        for byte in value.to_be_bytes() {
+            self.write(byte, line);
+        }
+    }
+
+    pub fn read_u16(&self, offset: usize) -> u16 {
+        u16::from_be_bytes([self.code[offset], self.code[offset + 1]])
+    }
+
+    // Identical constants, most often variable and property names, share a slot.
+    pub fn add_constant(&mut self, value: LiteralValue) -> usize {
+        let existing = self.constants.iter().position(|constant| match (constant, &value) {
+            (LiteralValue::String(a), LiteralValue::String(b)) => a == b,
+            (LiteralValue::Number(a), LiteralValue::Number(b)) => a.to_bits() == b.to_bits(),
+            _ => false,
+        });
+
+        existing.unwrap_or_else(|| {
+            self.constants.push(value);
+            self.constants.len() - 1
+        })
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Prototype {
+    pub name: String,
+    pub arity: usize,
+    pub upvalues: usize,
+    pub chunk: Chunk,
+}
+
+// Every function of a compiled program, the top-level script comes first.
+// `OpCode::Closure` refers to functions by their index in this list.
+#[derive(Clone, Debug, Default)]
+pub struct Program {
+    pub functions: Vec<Prototype>,
+}
+
+impl Program {
+    pub fn script(&self) -> &Prototype {
+        &self.functions[0]
+    }
+}
@@ -0,0 +1,149 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::fmt::{Debug, Display, Formatter};
+use std::rc::Rc;
+use crate::error::RuntimeError;
+use crate::function::{LoxCallable, LoxFunction};
+use crate::interpreter::Interpreter;
+use crate::object::Heap;
+use crate::symbol::Symbol;
+use crate::token::Token;
+use crate::token_type::LiteralValue;
+
+pub struct LoxClass {
+    pub name: String,
+    superclass: Option<Rc<LoxClass>>,
+    methods: HashMap<Symbol, Rc<LoxFunction>>,
+}
+
+impl LoxClass {
+    pub fn new(
+        name: String,
+        superclass: Option<Rc<LoxClass>>,
+        methods: HashMap<Symbol, Rc<LoxFunction>>,
+    ) -> LoxClass {
+        LoxClass {
+            name,
+            superclass,
+            methods,
+        }
+    }
+
+    pub fn find_method(&self, name: &Symbol) -> Option<Rc<LoxFunction>> {
+        if let Some(method) = self.methods.get(name) {
+            return Some(Rc::clone(method));
+        }
+
+        match &self.superclass {
+            Some(superclass) => superclass.find_method(name),
+            None => None,
+        }
+    }
+
+    pub fn methods(&self) -> impl Iterator<Item = &Rc<LoxFunction>> {
+        self.methods.values()
+    }
+
+    pub fn superclass(&self) -> Option<&LoxClass> {
+        self.superclass.as_deref()
+    }
+}
+
+impl LoxCallable for LoxClass {
+    fn arity(&self) -> usize {
+        match self.find_method(&Symbol::intern("init")) {
+            Some(initializer) => initializer.arity(),
+            None => 0,
+        }
+    }
+
+    fn call(self: Rc<Self>, interpreter: &mut Interpreter, arguments: Vec<LiteralValue>) -> Result<LiteralValue, RuntimeError> {
+        let instance = interpreter.alloc_instance(Rc::clone(&self));
+
+        if let Some(initializer) = self.find_method(&Symbol::intern("init")) {
+            Rc::new(initializer.bind(interpreter, instance.clone())).call(interpreter, arguments)?;
+        }
+
+        Ok(LiteralValue::Instance(instance))
+    }
+}
+
+impl Display for LoxClass {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name)
+    }
+}
+
+impl Debug for LoxClass {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        Display::fmt(self, f)
+    }
+}
+
+// The fields of an instance, kept on the interpreter's heap since they can
+// point back at the instance itself.
+#[derive(Default)]
+pub struct LoxInstance {
+    fields: HashMap<Symbol, LiteralValue>,
+}
+
+impl LoxInstance {
+    pub fn fields(&self) -> impl Iterator<Item = (&Symbol, &LiteralValue)> {
+        self.fields.iter()
+    }
+}
+
+// The value side of an instance: its class plus a handle to the fields.
+#[derive(Clone)]
+pub struct InstanceRef {
+    class: Rc<LoxClass>,
+    slot: usize,
+}
+
+impl InstanceRef {
+    pub fn new(class: Rc<LoxClass>, slot: usize) -> InstanceRef {
+        InstanceRef { class, slot }
+    }
+
+    pub fn class(&self) -> &LoxClass {
+        &self.class
+    }
+
+    pub fn slot(&self) -> usize {
+        self.slot
+    }
+
+    // Methods are bound to the instance they are read from.
+    pub fn get(&self, interpreter: &mut Interpreter, name: &Token) -> Result<LiteralValue, RuntimeError> {
+        if let Some(value) = interpreter.heap().instance(self).fields.get(&name.lexeme) {
+            return Ok(value.clone());
+        }
+
+        match self.class.find_method(&name.lexeme) {
+            Some(method) => Ok(LiteralValue::Function(Rc::new(method.bind(interpreter, self.clone())))),
+            None => Err(RuntimeError::new(name.clone(), format!("Undefined property '{}'.", name.lexeme))),
+        }
+    }
+
+    pub fn set(&self, heap: &mut Heap, name: &Token, value: LiteralValue) {
+        heap.instance_mut(self).fields.insert(name.lexeme.clone(), value);
+    }
+}
+
+impl PartialEq for InstanceRef {
+    fn eq(&self, other: &Self) -> bool {
+        self.slot == other.slot
+    }
+}
+
+impl Display for InstanceRef {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{} instance", self.class.name)
+    }
+}
+
+impl Debug for InstanceRef {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        Display::fmt(self, f)
+    }
+}
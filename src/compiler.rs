@@ -0,0 +1,684 @@
+use std::rc::Rc;
+use crate::chunk::{Chunk, OpCode, Program, Prototype};
+use crate::error::CompileError;
+use crate::expr::{Assign, Binary, Call, Expr, Get, GetIndex, Grouping, Interpolation, List, Literal, Logical, Map, Set, SetIndex, Super, This, Unary, Variable, Visitor as ExprVisitor};
+use crate::stmt::{Block, Class, Expression, Function, If, Print, Return, Stmt, Var, While, Visitor as StmtVisitor};
+use crate::symbol::Symbol;
+use crate::token::Token;
+use crate::token_type::{LiteralValue, TokenType};
+
+const MAX_LOCALS: usize = 256;
+const MAX_UPVALUES: usize = 256;
+
+#[derive(Clone, Copy, PartialEq)]
+enum FunctionKind {
+    Script,
+    Function,
+    Initializer,
+    Method,
+}
+
+struct Local {
+    name: String,
+    depth: usize,
+    captured: bool,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+struct UpvalueRef {
+    index: u8,
+    is_local: bool,
+}
+
+// Bookkeeping for the function whose body is being compiled, one per level
+// of nesting.
+struct FunctionState {
+    function: usize,
+    kind: FunctionKind,
+    locals: Vec<Local>,
+    upvalues: Vec<UpvalueRef>,
+    scope_depth: usize,
+}
+
+enum Access {
+    Get,
+    Set,
+}
+
+// Lowers a resolved program into bytecode. Locals live in stack slots and
+// variables captured by closures become upvalues, anything declared at the
+// top level is a global looked up by name.
+pub struct Compiler {
+    functions: Vec<Prototype>,
+    states: Vec<FunctionState>,
+    line: usize,
+}
+
+impl ExprVisitor<Result<(), CompileError>> for Compiler {
+    fn visit_assign_expr(&mut self, expr: &Assign) -> Result<(), CompileError> {
+        self.expression(&expr.value)?;
+        self.named_variable(&expr.name, Access::Set)
+    }
+
+    fn visit_binary_expr(&mut self, expr: &Binary) -> Result<(), CompileError> {
+        self.expression(&expr.left)?;
+        self.expression(&expr.right)?;
+
+        let op = match expr.operator.token_type {
+            TokenType::EqualEqual => OpCode::Equal,
+            TokenType::BangEqual => OpCode::NotEqual,
+            TokenType::Greater => OpCode::Greater,
+            TokenType::GreaterEqual => OpCode::GreaterEqual,
+            TokenType::Less => OpCode::Less,
+            TokenType::LessEqual => OpCode::LessEqual,
+            TokenType::Plus => OpCode::Add,
+            TokenType::Minus => OpCode::Subtract,
+            TokenType::Star => OpCode::Multiply,
+            TokenType::Slash => OpCode::Divide,
+            _ => return Err(CompileError::new(expr.operator.line, "Invalid binary operator.")),
+        };
+
+        self.line = expr.operator.line;
+        self.emit(op);
+        Ok(())
+    }
+
+    fn visit_call_expr(&mut self, expr: &Call) -> Result<(), CompileError> {
+        // A method call skips creating the bound method, and it is the only
+        // way to reach the built-in methods of a map.
+        if let Expr::Get(get) = &*expr.callee {
+            self.expression(&get.object)?;
+            for argument in &expr.arguments {
+                self.expression(argument)?;
+            }
+
+            self.line = expr.paren.line;
+            let name = self.identifier(&get.name.lexeme)?;
+            self.emit_with_u16(OpCode::Invoke, name);
+            self.emit_byte(expr.arguments.len() as u8);
+            return Ok(());
+        }
+
+        self.expression(&expr.callee)?;
+
+        for argument in &expr.arguments {
+            self.expression(argument)?;
+        }
+
+        self.line = expr.paren.line;
+        self.emit(OpCode::Call);
+        self.emit_byte(expr.arguments.len() as u8);
+        Ok(())
+    }
+
+    fn visit_get_expr(&mut self, expr: &Get) -> Result<(), CompileError> {
+        self.expression(&expr.object)?;
+
+        self.line = expr.name.line;
+        let name = self.identifier(&expr.name.lexeme)?;
+        self.emit_with_u16(OpCode::GetProperty, name);
+        Ok(())
+    }
+
+    fn visit_grouping_expr(&mut self, expr: &Grouping) -> Result<(), CompileError> {
+        self.expression(&expr.expression)
+    }
+
+    fn visit_get_index_expr(&mut self, expr: &GetIndex) -> Result<(), CompileError> {
+        self.expression(&expr.object)?;
+        self.expression(&expr.index)?;
+
+        self.line = expr.bracket.line;
+        self.emit(OpCode::GetIndex);
+        Ok(())
+    }
+
+    fn visit_interpolation_expr(&mut self, expr: &Interpolation) -> Result<(), CompileError> {
+        for part in &expr.parts {
+            self.expression(part)?;
+        }
+
+        self.line = expr.start.line;
+        let count = u8::try_from(expr.parts.len())
+            .map_err(|_| CompileError::new(self.line, "Too many parts in interpolated string."))?;
+        self.emit(OpCode::Interpolate);
+        self.emit_byte(count);
+        Ok(())
+    }
+
+    fn visit_list_expr(&mut self, expr: &List) -> Result<(), CompileError> {
+        for element in &expr.elements {
+            self.expression(element)?;
+        }
+
+        self.line = expr.bracket.line;
+        let count = u16::try_from(expr.elements.len())
+            .map_err(|_| CompileError::new(self.line, "Too many elements in list literal."))?;
+        self.emit_with_u16(OpCode::BuildList, count);
+        Ok(())
+    }
+
+    fn visit_literal_expr(&mut self, expr: &Literal) -> Result<(), CompileError> {
+        match &expr.value {
+            LiteralValue::Nil => self.emit(OpCode::Nil),
+            LiteralValue::Bool(true) => self.emit(OpCode::True),
+            LiteralValue::Bool(false) => self.emit(OpCode::False),
+            value => {
+                let constant = self.make_constant(value.clone())?;
+                self.emit_with_u16(OpCode::Constant, constant);
+            }
+        }
+        Ok(())
+    }
+
+    fn visit_logical_expr(&mut self, expr: &Logical) -> Result<(), CompileError> {
+        self.expression(&expr.left)?;
+        self.line = expr.operator.line;
+
+        // Leave the left operand on the stack when it already decides the result.
+        if expr.operator.token_type == TokenType::Or {
+            let else_jump = self.emit_jump(OpCode::JumpIfFalse);
+            let end_jump = self.emit_jump(OpCode::Jump);
+
+            self.patch_jump(else_jump)?;
+            self.emit(OpCode::Pop);
+            self.expression(&expr.right)?;
+            self.patch_jump(end_jump)
+        } else {
+            let end_jump = self.emit_jump(OpCode::JumpIfFalse);
+
+            self.emit(OpCode::Pop);
+            self.expression(&expr.right)?;
+            self.patch_jump(end_jump)
+        }
+    }
+
+    fn visit_map_expr(&mut self, expr: &Map) -> Result<(), CompileError> {
+        for (key, value) in &expr.entries {
+            self.expression(key)?;
+            self.expression(value)?;
+        }
+
+        self.line = expr.brace.line;
+        let count = u16::try_from(expr.entries.len())
+            .map_err(|_| CompileError::new(self.line, "Too many entries in map literal."))?;
+        self.emit_with_u16(OpCode::BuildMap, count);
+        Ok(())
+    }
+
+    fn visit_set_expr(&mut self, expr: &Set) -> Result<(), CompileError> {
+        self.expression(&expr.object)?;
+        self.expression(&expr.value)?;
+
+        self.line = expr.name.line;
+        let name = self.identifier(&expr.name.lexeme)?;
+        self.emit_with_u16(OpCode::SetProperty, name);
+        Ok(())
+    }
+
+    fn visit_set_index_expr(&mut self, expr: &SetIndex) -> Result<(), CompileError> {
+        self.expression(&expr.object)?;
+        self.expression(&expr.index)?;
+        self.expression(&expr.value)?;
+
+        self.line = expr.bracket.line;
+        self.emit(OpCode::SetIndex);
+        Ok(())
+    }
+
+    fn visit_super_expr(&mut self, expr: &Super) -> Result<(), CompileError> {
+        self.named_variable(&Compiler::synthetic(&expr.keyword, "this"), Access::Get)?;
+        self.named_variable(&expr.keyword, Access::Get)?;
+
+        self.line = expr.method.line;
+        let name = self.identifier(&expr.method.lexeme)?;
+        self.emit_with_u16(OpCode::GetSuper, name);
+        Ok(())
+    }
+
+    fn visit_this_expr(&mut self, expr: &This) -> Result<(), CompileError> {
+        self.named_variable(&expr.keyword, Access::Get)
+    }
+
+    fn visit_unary_expr(&mut self, expr: &Unary) -> Result<(), CompileError> {
+        self.expression(&expr.right)?;
+
+        self.line = expr.operator.line;
+        match expr.operator.token_type {
+            TokenType::Minus => self.emit(OpCode::Negate),
+            TokenType::Bang => self.emit(OpCode::Not),
+            _ => return Err(CompileError::new(expr.operator.line, "Invalid unary operator.")),
+        }
+        Ok(())
+    }
+
+    fn visit_variable_expr(&mut self, expr: &Variable) -> Result<(), CompileError> {
+        self.named_variable(&expr.name, Access::Get)
+    }
+}
+
+impl StmtVisitor<Result<(), CompileError>> for Compiler {
+    fn visit_block_stmt(&mut self, stmt: &Block) -> Result<(), CompileError> {
+        self.begin_scope();
+        self.statements(&stmt.statements)?;
+        self.end_scope();
+        Ok(())
+    }
+
+    fn visit_class_stmt(&mut self, stmt: &Class) -> Result<(), CompileError> {
+        self.line = stmt.name.line;
+        let name = self.identifier(&stmt.name.lexeme)?;
+        self.declare_variable(&stmt.name)?;
+
+        self.emit_with_u16(OpCode::Class, name);
+        self.define_variable(name);
+
+        // The superclass stays on the stack as the `super` local that the
+        // methods close over.
+        if let Some(superclass) = &stmt.superclass {
+            self.visit_variable_expr(superclass)?;
+
+            self.begin_scope();
+            self.add_local("super")?;
+
+            self.named_variable(&stmt.name, Access::Get)?;
+            self.line = superclass.name.line;
+            self.emit(OpCode::Inherit);
+        }
+
+        self.named_variable(&stmt.name, Access::Get)?;
+
+        for method in &stmt.methods {
+            let kind = if method.name.lexeme == "init" {
+                FunctionKind::Initializer
+            } else {
+                FunctionKind::Method
+            };
+
+            let name = self.identifier(&method.name.lexeme)?;
+            self.function(method, kind)?;
+            self.line = method.name.line;
+            self.emit_with_u16(OpCode::Method, name);
+        }
+
+        self.emit(OpCode::Pop);
+
+        if stmt.superclass.is_some() {
+            self.end_scope();
+        }
+        Ok(())
+    }
+
+    fn visit_expression_stmt(&mut self, stmt: &Expression) -> Result<(), CompileError> {
+        self.expression(&stmt.expression)?;
+        self.emit(OpCode::Pop);
+        Ok(())
+    }
+
+    fn visit_function_stmt(&mut self, stmt: &Rc<Function>) -> Result<(), CompileError> {
+        self.line = stmt.name.line;
+        let name = self.identifier(&stmt.name.lexeme)?;
+        self.declare_variable(&stmt.name)?;
+
+        // The local is in scope before the body is compiled so it can recurse.
+        self.function(stmt, FunctionKind::Function)?;
+        self.define_variable(name);
+        Ok(())
+    }
+
+    fn visit_if_stmt(&mut self, stmt: &If) -> Result<(), CompileError> {
+        self.expression(&stmt.condition)?;
+
+        let then_jump = self.emit_jump(OpCode::JumpIfFalse);
+        self.emit(OpCode::Pop);
+        self.statement(&stmt.then_branch)?;
+
+        let else_jump = self.emit_jump(OpCode::Jump);
+        self.patch_jump(then_jump)?;
+        self.emit(OpCode::Pop);
+
+        if let Some(else_branch) = &stmt.else_branch {
+            self.statement(else_branch)?;
+        }
+        self.patch_jump(else_jump)
+    }
+
+    fn visit_print_stmt(&mut self, stmt: &Print) -> Result<(), CompileError> {
+        self.expression(&stmt.expression)?;
+        self.emit(OpCode::Print);
+        Ok(())
+    }
+
+    fn visit_return_stmt(&mut self, stmt: &Return) -> Result<(), CompileError> {
+        self.line = stmt.keyword.line;
+
+        match &stmt.value {
+            Some(value) => {
+                self.expression(value)?;
+                self.emit(OpCode::Return);
+            }
+            None => self.emit_return(),
+        }
+        Ok(())
+    }
+
+    fn visit_var_stmt(&mut self, stmt: &Var) -> Result<(), CompileError> {
+        self.line = stmt.name.line;
+        let name = self.identifier(&stmt.name.lexeme)?;
+        self.declare_variable(&stmt.name)?;
+
+        match &stmt.initializer {
+            Some(initializer) => self.expression(initializer)?,
+            None => self.emit(OpCode::Nil),
+        }
+
+        self.define_variable(name);
+        Ok(())
+    }
+
+    fn visit_while_stmt(&mut self, stmt: &While) -> Result<(), CompileError> {
+        let loop_start = self.chunk().code.len();
+        self.expression(&stmt.condition)?;
+
+        let exit_jump = self.emit_jump(OpCode::JumpIfFalse);
+        self.emit(OpCode::Pop);
+        self.statement(&stmt.body)?;
+        self.emit_loop(loop_start)?;
+
+        self.patch_jump(exit_jump)?;
+        self.emit(OpCode::Pop);
+        Ok(())
+    }
+}
+
+impl Default for Compiler {
+    fn default() -> Self {
+        Compiler::new()
+    }
+}
+
+impl Compiler {
+    pub fn new() -> Compiler {
+        Compiler {
+            functions: Vec::new(),
+            states: Vec::new(),
+            line: 1,
+        }
+    }
+
+    pub fn compile(mut self, stmts: &[Stmt]) -> Result<Program, CompileError> {
+        self.begin_function("script", 0, FunctionKind::Script);
+        self.statements(stmts)?;
+        self.emit_return();
+        self.states.pop();
+
+        Ok(Program {
+            functions: self.functions,
+        })
+    }
+
+    fn statements(&mut self, stmts: &[Stmt]) -> Result<(), CompileError> {
+        for stmt in stmts {
+            self.statement(stmt)?;
+        }
+        Ok(())
+    }
+
+    fn statement(&mut self, stmt: &Stmt) -> Result<(), CompileError> {
+        stmt.accept(self)
+    }
+
+    fn expression(&mut self, expr: &Expr) -> Result<(), CompileError> {
+        expr.accept(self)
+    }
+
+    fn begin_function(&mut self, name: &str, arity: usize, kind: FunctionKind) {
+        self.functions.push(Prototype {
+            name: name.to_string(),
+            arity,
+            ..Prototype::default()
+        });
+
+        // Slot zero holds the function being called, or the receiver of a method.
+        let receiver = match kind {
+            FunctionKind::Method | FunctionKind::Initializer => "this",
+            _ => "",
+        };
+
+        self.states.push(FunctionState {
+            function: self.functions.len() - 1,
+            kind,
+            locals: vec![Local {
+                name: receiver.to_string(),
+                depth: 0,
+                captured: false,
+            }],
+            upvalues: Vec::new(),
+            scope_depth: if kind == FunctionKind::Script { 0 } else { 1 },
+        });
+    }
+
+    fn function(&mut self, declaration: &Function, kind: FunctionKind) -> Result<(), CompileError> {
+        self.begin_function(&declaration.name.lexeme, declaration.params.len(), kind);
+
+        for param in &declaration.params {
+            self.declare_variable(param)?;
+        }
+
+        self.statements(&declaration.body)?;
+        self.emit_return();
+
+        let state = self.states.pop().expect("function state");
+        self.functions[state.function].upvalues = state.upvalues.len();
+
+        self.line = declaration.name.line;
+        let function = u16::try_from(state.function)
+            .map_err(|_| CompileError::new(self.line, "Too many functions in one program."))?;
+        self.emit_with_u16(OpCode::Closure, function);
+
+        for upvalue in state.upvalues {
+            self.emit_byte(upvalue.is_local as u8);
+            self.emit_byte(upvalue.index);
+        }
+        Ok(())
+    }
+
+    fn begin_scope(&mut self) {
+        self.state_mut().scope_depth += 1;
+    }
+
+    fn end_scope(&mut self) {
+        let state = self.state_mut();
+        state.scope_depth -= 1;
+
+        let depth = state.scope_depth;
+        let mut captured = Vec::new();
+        while state.locals.last().is_some_and(|local| local.depth > depth) {
+            captured.push(state.locals.pop().expect("local").captured);
+        }
+
+        for captured in captured {
+            self.emit(if captured { OpCode::CloseUpvalue } else { OpCode::Pop });
+        }
+    }
+
+    // Locals are added before their initializer runs; the resolver has
+    // already rejected reads of a variable inside its own initializer.
+    fn declare_variable(&mut self, name: &Token) -> Result<(), CompileError> {
+        if self.state().scope_depth == 0 {
+            return Ok(());
+        }
+
+        self.add_local(&name.lexeme)
+    }
+
+    fn add_local(&mut self, name: &str) -> Result<(), CompileError> {
+        if self.state().locals.len() == MAX_LOCALS {
+            return Err(CompileError::new(self.line, "Too many local variables in function."));
+        }
+
+        let state = self.state_mut();
+        let depth = state.scope_depth;
+        state.locals.push(Local {
+            name: name.to_string(),
+            depth,
+            captured: false,
+        });
+        Ok(())
+    }
+
+    fn define_variable(&mut self, name: u16) {
+        if self.state().scope_depth == 0 {
+            self.emit_with_u16(OpCode::DefineGlobal, name);
+        }
+    }
+
+    fn named_variable(&mut self, name: &Token, access: Access) -> Result<(), CompileError> {
+        self.line = name.line;
+        let current = self.states.len() - 1;
+
+        let (get, set, operand) = if let Some(slot) = self.resolve_local(current, &name.lexeme) {
+            (OpCode::GetLocal, OpCode::SetLocal, slot as u16)
+        } else if let Some(index) = self.resolve_upvalue(current, &name.lexeme)? {
+            (OpCode::GetUpvalue, OpCode::SetUpvalue, index as u16)
+        } else {
+            let constant = self.identifier(&name.lexeme)?;
+            let op = match access {
+                Access::Get => OpCode::GetGlobal,
+                Access::Set => OpCode::SetGlobal,
+            };
+            self.emit_with_u16(op, constant);
+            return Ok(());
+        };
+
+        self.emit(match access {
+            Access::Get => get,
+            Access::Set => set,
+        });
+        self.emit_byte(operand as u8);
+        Ok(())
+    }
+
+    fn resolve_local(&self, state: usize, name: &str) -> Option<u8> {
+        self.states[state]
+            .locals
+            .iter()
+            .rposition(|local| local.name == name)
+            .map(|slot| slot as u8)
+    }
+
+    // Walks outwards through the enclosing functions, threading the capture
+    // through every function in between.
+    fn resolve_upvalue(&mut self, state: usize, name: &str) -> Result<Option<u8>, CompileError> {
+        if state == 0 {
+            return Ok(None);
+        }
+
+        if let Some(slot) = self.resolve_local(state - 1, name) {
+            self.states[state - 1].locals[slot as usize].captured = true;
+            return self.add_upvalue(state, slot, true).map(Some);
+        }
+
+        match self.resolve_upvalue(state - 1, name)? {
+            Some(index) => self.add_upvalue(state, index, false).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn add_upvalue(&mut self, state: usize, index: u8, is_local: bool) -> Result<u8, CompileError> {
+        let upvalue = UpvalueRef { index, is_local };
+        let upvalues = &mut self.states[state].upvalues;
+
+        if let Some(existing) = upvalues.iter().position(|existing| *existing == upvalue) {
+            return Ok(existing as u8);
+        }
+        if upvalues.len() == MAX_UPVALUES {
+            return Err(CompileError::new(self.line, "Too many closure variables in function."));
+        }
+
+        upvalues.push(upvalue);
+        Ok((upvalues.len() - 1) as u8)
+    }
+
+    fn identifier(&mut self, name: &str) -> Result<u16, CompileError> {
+        self.make_constant(LiteralValue::String(Symbol::intern(name)))
+    }
+
+    fn make_constant(&mut self, value: LiteralValue) -> Result<u16, CompileError> {
+        let constant = self.chunk().add_constant(value);
+        u16::try_from(constant).map_err(|_| CompileError::new(self.line, "Too many constants in one chunk."))
+    }
+
+    fn emit(&mut self, op: OpCode) {
+        self.emit_byte(op as u8);
+    }
+
+    fn emit_byte(&mut self, byte: u8) {
+        let line = self.line;
+        self.chunk().write(byte, line);
+    }
+
+    fn emit_with_u16(&mut self, op: OpCode, operand: u16) {
+        let line = self.line;
+        self.emit(op);
+        self.chunk().write_u16(operand, line);
+    }
+
+    fn emit_return(&mut self) {
+        if self.state().kind == FunctionKind::Initializer {
+            self.emit(OpCode::GetLocal);
+            self.emit_byte(0);
+        } else {
+            self.emit(OpCode::Nil);
+        }
+        self.emit(OpCode::Return);
+    }
+
+    // Emits a jump with a placeholder offset and returns where to patch it.
+    fn emit_jump(&mut self, op: OpCode) -> usize {
+        self.emit_with_u16(op, u16::MAX);
+        self.chunk().code.len() - 2
+    }
+
+    fn patch_jump(&mut self, offset: usize) -> Result<(), CompileError> {
+        let jump = self.chunk().code.len() - offset - 2;
+        let jump = u16::try_from(jump).map_err(|_| CompileError::new(self.line, "Too much code to jump over."))?;
+
+        let [high, low] = jump.to_be_bytes();
+        let chunk = self.chunk();
+        chunk.code[offset] = high;
+        chunk.code[offset + 1] = low;
+        Ok(())
+    }
+
+    fn emit_loop(&mut self, loop_start: usize) -> Result<(), CompileError> {
+        // The offset also skips the loop instruction's own operand.
+        let offset = self.chunk().code.len() + 3 - loop_start;
+        let offset = u16::try_from(offset).map_err(|_| CompileError::new(self.line, "Loop body too large."))?;
+
+        self.emit_with_u16(OpCode::Loop, offset);
+        Ok(())
+    }
+
+    fn state(&self) -> &FunctionState {
+        self.states.last().expect("function state")
+    }
+
+    fn state_mut(&mut self) -> &mut FunctionState {
+        self.states.last_mut().expect("function state")
+    }
+
+    fn chunk(&mut self) -> &mut Chunk {
+        let function = self.state().function;
+        &mut self.functions[function].chunk
+    }
+
+    // A token for a name the program never spells out, like the `this` that
+    // `super` implicitly binds to.
+    fn synthetic(token: &Token, lexeme: &str) -> Token {
+        Token {
+            lexeme: Symbol::intern(lexeme),
+            ..token.clone()
+        }
+    }
+}
@@ -0,0 +1,107 @@
+use crate::error::LoxError;
+use crate::token::Span;
+use crate::token_type::TokenType;
+use std::fmt::Write;
+
+const RED: &str = "\x1b[1;31m";
+const BLUE: &str = "\x1b[1;34m";
+const BOLD: &str = "\x1b[1m";
+const RESET: &str = "\x1b[0m";
+
+// A rustc-style rendering of an error: a header, the offending source line
+// with the span underlined and an optional label next to the carets.
+pub struct Diagnostic {
+    pub kind: &'static str,
+    pub message: String,
+    pub line: usize,
+    pub column: Option<usize>,
+    pub span: Span,
+    pub label: Option<String>,
+}
+
+impl From<&LoxError> for Diagnostic {
+    fn from(err: &LoxError) -> Self {
+        let (kind, label) = match err {
+            LoxError::Scan(_) => ("scan", None),
+            LoxError::Parse(err) => {
+                let label = if err.token.token_type == TokenType::Eof {
+                    "unexpected end of file".to_string()
+                } else {
+                    format!("unexpected '{}'", err.token.lexeme)
+                };
+                ("parse", Some(label))
+            }
+            LoxError::Resolve(_) => ("resolve", None),
+            LoxError::Compile(_) => ("compile", None),
+            LoxError::Runtime(_) => ("runtime", None),
+        };
+
+        Diagnostic {
+            kind,
+            message: err.message().to_string(),
+            line: err.line(),
+            column: err.column(),
+            span: err.span(),
+            label,
+        }
+    }
+}
+
+impl Diagnostic {
+    pub fn render(&self, source: &str, filename: &str, color: bool) -> String {
+        let paint = |style: &str, text: &str| {
+            if color {
+                format!("{}{}{}", style, text, RESET)
+            } else {
+                text.to_string()
+            }
+        };
+
+        let source_line = source.lines().nth(self.line.saturating_sub(1)).unwrap_or("");
+        let gutter = " ".repeat(self.line.to_string().len());
+
+        // Only the part of the span on the line being shown is underlined.
+        let line_start: usize = source
+            .split_inclusive('\n')
+            .take(self.line.saturating_sub(1))
+            .map(str::len)
+            .sum();
+        let line_end = line_start + source_line.len();
+        let underlined = source
+            .get(self.span.start.max(line_start)..self.span.end.min(line_end))
+            .map_or(0, |text| text.chars().count());
+        let carets = "^".repeat(underlined.max(1));
+
+        let mut out = String::new();
+        let _ = writeln!(
+            out,
+            "{}{}",
+            paint(RED, &format!("error[{}]", self.kind)),
+            paint(BOLD, &format!(": {}", self.message))
+        );
+        let location = match self.column {
+            Some(column) => format!("{}:{}:{}", filename, self.line, column),
+            None => format!("{}:{}", filename, self.line),
+        };
+        let _ = writeln!(out, "{}{} {}", gutter, paint(BLUE, "-->"), location);
+        let _ = writeln!(out, "{} {}", gutter, paint(BLUE, "|"));
+        let _ = writeln!(out, "{} {} {}", paint(BLUE, &self.line.to_string()), paint(BLUE, "|"), source_line);
+
+        // Without a column there is nothing to point at.
+        if let Some(column) = self.column {
+            // Tabs are copied so the carets line up however wide they render.
+            let padding: String = source_line
+                .chars()
+                .take(column.saturating_sub(1))
+                .map(|c| if c == '\t' { '\t' } else { ' ' })
+                .collect();
+            let underline = match &self.label {
+                Some(label) => format!("{} {}", carets, label),
+                None => carets,
+            };
+            let _ = writeln!(out, "{} {} {}{}", gutter, paint(BLUE, "|"), padding, paint(RED, &underline));
+        }
+
+        out
+    }
+}
@@ -0,0 +1,129 @@
+use std::fmt::Write;
+use crate::chunk::{Chunk, OpCode, Program};
+use crate::token_type::LiteralValue;
+use crate::util::Utils;
+
+// Renders compiled bytecode one instruction per line: the byte offset, the
+// source line (or `|` when unchanged), the opcode and its decoded operands.
+pub struct Disassembler<'a> {
+    program: &'a Program,
+}
+
+impl<'a> Disassembler<'a> {
+    pub fn new(program: &'a Program) -> Disassembler<'a> {
+        Disassembler { program }
+    }
+
+    pub fn disassemble(&self) -> String {
+        let mut out = String::new();
+
+        for (index, function) in self.program.functions.iter().enumerate() {
+            if index == 0 {
+                let _ = writeln!(out, "== <script> ==");
+            } else {
+                let _ = writeln!(
+                    out,
+                    "== {} (arity {}, upvalues {}) ==",
+                    function.name, function.arity, function.upvalues
+                );
+            }
+
+            self.chunk(&function.chunk, &mut out);
+            out.push('\n');
+        }
+
+        out
+    }
+
+    pub fn chunk(&self, chunk: &Chunk, out: &mut String) {
+        let mut offset = 0;
+        while offset < chunk.code.len() {
+            offset = self.instruction(chunk, offset, out);
+        }
+    }
+
+    // Writes the instruction at `offset` and returns the offset of the next one.
+    pub fn instruction(&self, chunk: &Chunk, offset: usize, out: &mut String) -> usize {
+        let _ = write!(out, "{:04} ", offset);
+        if offset > 0 && chunk.lines[offset] == chunk.lines[offset - 1] {
+            let _ = write!(out, "   | ");
+        } else {
+            let _ = write!(out, "{:>4} ", chunk.lines[offset]);
+        }
+
+        let op = match OpCode::from_byte(chunk.code[offset]) {
+            Some(op) => op,
+            None => {
+                let _ = writeln!(out, "<unknown opcode {}>", chunk.code[offset]);
+                return offset + 1;
+            }
+        };
+
+        match op {
+            OpCode::Constant
+            | OpCode::GetGlobal
+            | OpCode::DefineGlobal
+            | OpCode::SetGlobal
+            | OpCode::GetProperty
+            | OpCode::SetProperty
+            | OpCode::GetSuper
+            | OpCode::Class
+            | OpCode::Method => {
+                let constant = chunk.read_u16(offset + 1) as usize;
+                let value = chunk.constants.get(constant).map_or("<invalid>".to_string(), Disassembler::constant);
+                let _ = writeln!(out, "{:<16} {:4} {}", op, constant, value);
+                offset + 3
+            }
+            OpCode::GetLocal | OpCode::SetLocal | OpCode::GetUpvalue | OpCode::SetUpvalue | OpCode::Call | OpCode::Interpolate => {
+                let _ = writeln!(out, "{:<16} {:4}", op, chunk.code[offset + 1]);
+                offset + 2
+            }
+            OpCode::Invoke => {
+                let constant = chunk.read_u16(offset + 1) as usize;
+                let value = chunk.constants.get(constant).map_or("<invalid>".to_string(), Disassembler::constant);
+                let _ = writeln!(out, "{:<16} ({} args) {:4} {}", op, chunk.code[offset + 3], constant, value);
+                offset + 4
+            }
+            OpCode::BuildList | OpCode::BuildMap => {
+                let _ = writeln!(out, "{:<16} {:4}", op, chunk.read_u16(offset + 1));
+                offset + 3
+            }
+            OpCode::Jump | OpCode::JumpIfFalse | OpCode::Loop => {
+                let jump = chunk.read_u16(offset + 1) as usize;
+                let target = if op == OpCode::Loop {
+                    (offset + 3).saturating_sub(jump)
+                } else {
+                    offset + 3 + jump
+                };
+                let _ = writeln!(out, "{:<16} {:4} -> {:04}", op, jump, target);
+                offset + 3
+            }
+            OpCode::Closure => {
+                let function = chunk.read_u16(offset + 1) as usize;
+                let prototype = self.program.functions.get(function);
+                let name = prototype.map_or("<invalid>", |prototype| &prototype.name);
+                let _ = writeln!(out, "{:<16} {:4} <fn {}>", op, function, name);
+
+                let mut offset = offset + 3;
+                for _ in 0..prototype.map_or(0, |prototype| prototype.upvalues) {
+                    let kind = if chunk.code[offset] == 1 { "local" } else { "upvalue" };
+                    let _ = writeln!(out, "{:04}    |   {:<14} {:4}", offset, kind, chunk.code[offset + 1]);
+                    offset += 2;
+                }
+                offset
+            }
+            _ => {
+                let _ = writeln!(out, "{}", op);
+                offset + 1
+            }
+        }
+    }
+
+    // Strings are quoted so they can't be mistaken for numbers or keywords.
+    fn constant(value: &LiteralValue) -> String {
+        match value {
+            LiteralValue::String(string) => format!("{:?}", string),
+            value => Utils::print_literal(value),
+        }
+    }
+}
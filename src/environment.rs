@@ -1,51 +1,111 @@
-use std::cell::{Ref, RefCell};
 use std::collections::HashMap;
-use std::rc::Rc;
+use crate::error::RuntimeError;
+use crate::object::Heap;
+use crate::symbol::Symbol;
 use crate::token::Token;
 use crate::token_type::LiteralValue;
 
+// A handle to an `Environment` living on the interpreter's heap. Closures
+// capture these instead of owning their scope, so a function stored in the
+// scope it closes over is a cycle the collector can reclaim.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct EnvRef(usize);
+
 pub struct Environment {
-    enclosing: Option<Rc<RefCell<Environment>>>,
-    variables: HashMap<String, LiteralValue>,
+    enclosing: Option<EnvRef>,
+    variables: HashMap<Symbol, LiteralValue>,
 }
 
 impl Environment {
-    pub fn global() -> Environment {
+    pub fn new(enclosing: Option<EnvRef>) -> Environment {
         Environment {
-            enclosing: None,
+            enclosing,
             variables: HashMap::new(),
         }
     }
 
-    pub fn local(enclosing: Rc<RefCell<Environment>>) -> Environment {
-        Environment {
-            enclosing: Some(enclosing),
-            variables: HashMap::new(),
-        }
+    pub fn enclosing(&self) -> Option<EnvRef> {
+        self.enclosing
     }
 
-    pub fn define(&mut self, name: String, value: LiteralValue) {
+    pub fn variables(&self) -> impl Iterator<Item = (&Symbol, &LiteralValue)> {
+        self.variables.iter()
+    }
+
+    pub fn define(&mut self, name: Symbol, value: LiteralValue) {
         self.variables.insert(name, value);
     }
+}
+
+impl EnvRef {
+    pub fn new(slot: usize) -> EnvRef {
+        EnvRef(slot)
+    }
+
+    pub fn slot(self) -> usize {
+        self.0
+    }
+
+    pub fn define(self, heap: &mut Heap, name: Symbol, value: LiteralValue) {
+        heap.environment_mut(self).define(name, value);
+    }
 
-    pub fn assign(&mut self, name: Token, value: LiteralValue) -> Result<(), String> {
-        if self.variables.contains_key(&name.lexeme) {
-            self.variables.insert(name.lexeme, value);
-            Ok(())
-        } else if let Some(ref enclosing) = self.enclosing {
-            enclosing.borrow_mut().assign(name, value)
-        } else {
-            Err(format!("Variable '{}' not found.", name.lexeme))
+    pub fn assign(self, heap: &mut Heap, name: Token, value: LiteralValue) -> Result<(), RuntimeError> {
+        let mut environment = Some(self);
+
+        while let Some(current) = environment {
+            let scope = heap.environment_mut(current);
+            if let Some(variable) = scope.variables.get_mut(&name.lexeme) {
+                *variable = value;
+                return Ok(());
+            }
+            environment = scope.enclosing;
         }
+
+        let message = format!("Variable '{}' not found.", name.lexeme);
+        Err(RuntimeError::new(name, message))
     }
 
-    pub fn get(&self, name: Token) -> Result<LiteralValue, String> {
-        if let Some(value) = self.variables.get(&name.lexeme) {
-            Ok(value.clone())
-        } else if let Some(ref enclosing) = self.enclosing {
-            enclosing.borrow().get(name)
-        } else {
-            Err(format!("Undefined variable '{}'.", name.lexeme))
+    pub fn get(self, heap: &Heap, name: Token) -> Result<LiteralValue, RuntimeError> {
+        let mut environment = Some(self);
+
+        while let Some(current) = environment {
+            let scope = heap.environment(current);
+            if let Some(value) = scope.variables.get(&name.lexeme) {
+                return Ok(value.clone());
+            }
+            environment = scope.enclosing;
         }
+
+        let message = format!("Undefined variable '{}'.", name.lexeme);
+        Err(RuntimeError::new(name, message))
     }
-}
\ No newline at end of file
+
+    // The resolver already knows how many scopes up a local lives, so these
+    // skip straight to it instead of searching the chain by name.
+    pub fn get_at(self, heap: &Heap, distance: usize, name: &Symbol) -> Option<LiteralValue> {
+        let ancestor = self.ancestor(heap, distance)?;
+        heap.environment(ancestor).variables.get(name).cloned()
+    }
+
+    pub fn assign_at(self, heap: &mut Heap, distance: usize, name: Token, value: LiteralValue) -> Result<(), RuntimeError> {
+        match self.ancestor(heap, distance) {
+            Some(ancestor) => {
+                heap.environment_mut(ancestor).variables.insert(name.lexeme, value);
+                Ok(())
+            }
+            None => {
+                let message = format!("Variable '{}' not found.", name.lexeme);
+                Err(RuntimeError::new(name, message))
+            }
+        }
+    }
+
+    fn ancestor(self, heap: &Heap, distance: usize) -> Option<EnvRef> {
+        let mut environment = self;
+        for _ in 0..distance {
+            environment = heap.environment(environment).enclosing?;
+        }
+        Some(environment)
+    }
+}
@@ -1,11 +1,304 @@
-pub struct LoxError {}
+use crate::native::ValueType;
+use crate::token::{Span, Token};
+use crate::token_type::TokenType;
+use std::fmt::Display;
+use thiserror::Error;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ScanErrorKind {
+    UnexpectedCharacter,
+    UnterminatedString,
+    UnterminatedComment,
+    InvalidEscape,
+    Unreadable,
+}
+
+#[derive(Clone, Debug, Error)]
+#[error("[line {line}] Error: {message}")]
+pub struct ScanError {
+    pub kind: ScanErrorKind,
+    pub message: String,
+    pub lexeme: String,
+    pub line: usize,
+    pub column: usize,
+    pub span: Span,
+}
+
+#[derive(Clone, Debug, Error)]
+#[error("{}", report_at(token.line, token, message))]
+pub struct ParseError {
+    pub token: Box<Token>,
+    pub message: String,
+}
+
+#[derive(Clone, Debug, Error)]
+#[error("{}", report_at(token.line, token, message))]
+pub struct ResolveError {
+    pub token: Box<Token>,
+    pub message: String,
+}
+
+// Raised by the bytecode compiler when a program exceeds one of its limits.
+#[derive(Clone, Debug, Error)]
+#[error("[line {line}] Error: {message}")]
+pub struct CompileError {
+    pub line: usize,
+    pub message: String,
+}
+
+// The location is boxed to keep the results the tree-walker passes back
+// through every level of recursion small.
+#[derive(Clone, Debug, Error)]
+#[error("{message}\n[line {}]", location.line)]
+pub struct RuntimeError {
+    pub message: String,
+    pub location: Box<Location>,
+}
+
+// The VM only knows the line of the failing instruction, so `column` is
+// None for its errors and `span` empty.
+#[derive(Clone, Copy, Debug)]
+pub struct Location {
+    pub line: usize,
+    pub column: Option<usize>,
+    pub span: Span,
+}
+
+// Raised by a native function, the caller turns it into a `RuntimeError` at
+// the call site.
+#[derive(Clone, Debug, Error)]
+pub enum NativeError {
+    #[error("{function}() expects {expected} but got {found}.")]
+    ArgumentType { function: &'static str, expected: &'static str, found: ValueType },
+    #[error("{function}() can't convert {value} to a number.")]
+    Conversion { function: &'static str, value: String },
+    #[error("{function}() {message}.")]
+    Range { function: &'static str, message: String },
+    #[error("{function}() failed: {message}")]
+    Io { function: &'static str, message: String },
+}
+
+// Problems with a compiled program read back from disk.
+#[derive(Clone, Debug, Error, PartialEq)]
+pub enum BytecodeError {
+    #[error("not a compiled Lox program")]
+    BadMagic,
+    #[error("compiled with format version {found}, but this build only runs version {expected}")]
+    VersionMismatch { found: u16, expected: u16 },
+    #[error("the file is truncated")]
+    Truncated,
+    #[error("the file is corrupt: {0}")]
+    Corrupt(String),
+}
+
+#[derive(Clone, Debug, Error)]
+pub enum LoxError {
+    #[error(transparent)]
+    Scan(#[from] ScanError),
+    #[error(transparent)]
+    Parse(#[from] ParseError),
+    #[error(transparent)]
+    Resolve(#[from] ResolveError),
+    #[error(transparent)]
+    Compile(#[from] CompileError),
+    #[error(transparent)]
+    Runtime(#[from] RuntimeError),
+}
+
+impl ScanError {
+    pub fn unexpected_character(c: char, line: usize, column: usize, span: Span) -> ScanError {
+        ScanError {
+            kind: ScanErrorKind::UnexpectedCharacter,
+            message: format!("Unexpected character: {}", c),
+            lexeme: c.to_string(),
+            line,
+            column,
+            span,
+        }
+    }
+
+    pub fn unterminated_string(lexeme: String, line: usize, column: usize, span: Span) -> ScanError {
+        ScanError {
+            kind: ScanErrorKind::UnterminatedString,
+            message: "Unterminated string.".to_string(),
+            lexeme,
+            line,
+            column,
+            span,
+        }
+    }
+
+    pub fn invalid_escape(lexeme: String, line: usize, column: usize, span: Span) -> ScanError {
+        ScanError {
+            kind: ScanErrorKind::InvalidEscape,
+            message: format!("Invalid escape sequence: {}", lexeme),
+            lexeme,
+            line,
+            column,
+            span,
+        }
+    }
+
+    // The input stopped where it couldn't be read, e.g. on invalid UTF-8.
+    pub fn unreadable(reason: String, line: usize, column: usize, offset: usize) -> ScanError {
+        ScanError {
+            kind: ScanErrorKind::Unreadable,
+            message: format!("Failed to read input: {}", reason),
+            lexeme: String::new(),
+            line,
+            column,
+            span: Span {
+                start: offset,
+                end: offset,
+            },
+        }
+    }
+
+    // Points at the `/*` that was never closed.
+    pub fn unterminated_comment(line: usize, column: usize, start: usize) -> ScanError {
+        ScanError {
+            kind: ScanErrorKind::UnterminatedComment,
+            message: "Unterminated block comment.".to_string(),
+            lexeme: "/*".to_string(),
+            line,
+            column,
+            span: Span {
+                start,
+                end: start + 2,
+            },
+        }
+    }
+}
+
+impl ParseError {
+    pub fn new(token: Token, message: &str) -> ParseError {
+        ParseError {
+            token: Box::new(token),
+            message: message.to_string(),
+        }
+    }
+}
+
+impl ResolveError {
+    pub fn new(token: Token, message: &str) -> ResolveError {
+        ResolveError {
+            token: Box::new(token),
+            message: message.to_string(),
+        }
+    }
+}
+
+impl CompileError {
+    pub fn new(line: usize, message: &str) -> CompileError {
+        CompileError {
+            line,
+            message: message.to_string(),
+        }
+    }
+}
+
+impl RuntimeError {
+    pub fn new(token: Token, message: String) -> RuntimeError {
+        let location = Location {
+            line: token.line,
+            column: Some(token.column),
+            span: token.span,
+        };
+        RuntimeError {
+            message,
+            location: Box::new(location),
+        }
+    }
+
+    pub fn at_line(line: usize, message: String) -> RuntimeError {
+        let location = Location {
+            line,
+            column: None,
+            span: Span::default(),
+        };
+        RuntimeError {
+            message,
+            location: Box::new(location),
+        }
+    }
+}
 
 impl LoxError {
-    pub fn unexpected_character(line: usize, c: char) {
-        eprintln!("[line {}] Error: Unexpected character: {}", line, c)
+    pub fn message(&self) -> &str {
+        match self {
+            LoxError::Scan(err) => &err.message,
+            LoxError::Parse(err) => &err.message,
+            LoxError::Resolve(err) => &err.message,
+            LoxError::Compile(err) => &err.message,
+            LoxError::Runtime(err) => &err.message,
+        }
+    }
+
+    // Only the static errors after scanning are about a particular token.
+    pub fn token(&self) -> Option<&Token> {
+        match self {
+            LoxError::Parse(err) => Some(&err.token),
+            LoxError::Resolve(err) => Some(&err.token),
+            _ => None,
+        }
+    }
+
+    pub fn line(&self) -> usize {
+        match self {
+            LoxError::Scan(err) => err.line,
+            LoxError::Compile(err) => err.line,
+            LoxError::Runtime(err) => err.location.line,
+            _ => self.token().map_or(0, |token| token.line),
+        }
+    }
+
+    // None when only the line is known, like for errors from the VM.
+    pub fn column(&self) -> Option<usize> {
+        match self {
+            LoxError::Scan(err) => Some(err.column),
+            LoxError::Compile(_) => None,
+            LoxError::Runtime(err) => err.location.column,
+            _ => self.token().map(|token| token.column),
+        }
     }
 
-    pub fn unterminated_string(line: usize) {
-        eprintln!("[line {}] Error: Unterminated string.", line)
+    pub fn span(&self) -> Span {
+        match self {
+            LoxError::Scan(err) => err.span,
+            LoxError::Runtime(err) => err.location.span,
+            _ => self.token().map_or(Span::default(), |token| token.span),
+        }
+    }
+
+    // The default messages only carry the line to stay compatible with the
+    // reference implementation, this one also points at the exact column,
+    // e.g. `[line 3:14] Error at '+': Expect expression.`.
+    pub fn located(&self) -> String {
+        match self {
+            LoxError::Scan(err) => format!("[line {}:{}] Error: {}", err.line, err.column, err.message),
+            LoxError::Parse(err) => report_at(err.token.start(), &err.token, &err.message),
+            LoxError::Resolve(err) => report_at(err.token.start(), &err.token, &err.message),
+            LoxError::Compile(err) => err.to_string(),
+            LoxError::Runtime(err) => match err.location.column {
+                Some(column) => format!("{}\n[line {}:{}]", err.message, err.location.line, column),
+                None => err.to_string(),
+            },
+        }
+    }
+
+    // Static errors use the same exit codes as the reference implementation.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            LoxError::Runtime(_) => 70,
+            _ => 65,
+        }
+    }
+}
+
+fn report_at(location: impl Display, token: &Token, message: &str) -> String {
+    if token.token_type == TokenType::Eof {
+        format!("[line {}] Error at end: {}", location, message)
+    } else {
+        format!("[line {}] Error at '{}': {}", location, token.lexeme, message)
     }
 }
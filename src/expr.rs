@@ -1,19 +1,43 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
 use crate::token::Token;
 use crate::token_type::LiteralValue;
 
+// Identifies a single variable use in the tree so the resolver can record
+// how many scopes away its binding lives.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ExprId(usize);
+
+impl ExprId {
+    pub fn fresh() -> ExprId {
+        static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+        ExprId(NEXT_ID.fetch_add(1, Ordering::Relaxed))
+    }
+}
+
 #[derive(Clone, Debug)]
 pub enum Expr {
     Assign(Assign),
     Binary(Binary),
+    Call(Call),
+    Get(Get),
+    GetIndex(GetIndex),
     Grouping(Grouping),
+    Interpolation(Interpolation),
+    List(List),
     Literal(Literal),
+    Logical(Logical),
+    Map(Map),
+    Set(Set),
+    SetIndex(SetIndex),
+    Super(Super),
+    This(This),
     Unary(Unary),
     Variable(Variable),
 }
 
 #[derive(Clone, Debug)]
 pub struct Assign {
-    pub name: Token, pub value: Box<Expr>,
+    pub id: ExprId, pub name: Token, pub value: Box<Expr>,
 }
 
 #[derive(Clone, Debug)]
@@ -21,16 +45,75 @@ pub struct Binary {
     pub left: Box<Expr>, pub operator: Token, pub right: Box<Expr>,
 }
 
+#[derive(Clone, Debug)]
+pub struct Call {
+    pub callee: Box<Expr>, pub paren: Token, pub arguments: Vec<Expr>,
+}
+
+#[derive(Clone, Debug)]
+pub struct Get {
+    pub object: Box<Expr>, pub name: Token,
+}
+
+// `bracket` is the opening `[`, runtime errors point at it.
+#[derive(Clone, Debug)]
+pub struct GetIndex {
+    pub object: Box<Expr>, pub bracket: Token, pub index: Box<Expr>,
+}
+
 #[derive(Clone, Debug)]
 pub struct Grouping {
     pub expression: Box<Expr>,
 }
 
+// `parts` alternates between string literals and the interpolated
+// expressions, `start` is the token the string begins with.
+#[derive(Clone, Debug)]
+pub struct Interpolation {
+    pub start: Token, pub parts: Vec<Expr>,
+}
+
+#[derive(Clone, Debug)]
+pub struct List {
+    pub bracket: Token, pub elements: Vec<Expr>,
+}
+
 #[derive(Clone, Debug)]
 pub struct Literal {
     pub value: LiteralValue,
 }
 
+#[derive(Clone, Debug)]
+pub struct Logical {
+    pub left: Box<Expr>, pub operator: Token, pub right: Box<Expr>,
+}
+
+// `brace` is the opening `{`, `entries` pairs each key with its value.
+#[derive(Clone, Debug)]
+pub struct Map {
+    pub brace: Token, pub entries: Vec<(Expr, Expr)>,
+}
+
+#[derive(Clone, Debug)]
+pub struct Set {
+    pub object: Box<Expr>, pub name: Token, pub value: Box<Expr>,
+}
+
+#[derive(Clone, Debug)]
+pub struct SetIndex {
+    pub object: Box<Expr>, pub bracket: Token, pub index: Box<Expr>, pub value: Box<Expr>,
+}
+
+#[derive(Clone, Debug)]
+pub struct Super {
+    pub id: ExprId, pub keyword: Token, pub method: Token,
+}
+
+#[derive(Clone, Debug)]
+pub struct This {
+    pub id: ExprId, pub keyword: Token,
+}
+
 #[derive(Clone, Debug)]
 pub struct Unary {
     pub operator: Token, pub right: Box<Expr>,
@@ -38,14 +121,25 @@ pub struct Unary {
 
 #[derive(Clone, Debug)]
 pub struct Variable {
-    pub name: Token,
+    pub id: ExprId, pub name: Token,
 }
 
 pub trait Visitor<R> {
     fn visit_assign_expr(&mut self, expr: &Assign) -> R;
     fn visit_binary_expr(&mut self, expr: &Binary) -> R;
+    fn visit_call_expr(&mut self, expr: &Call) -> R;
+    fn visit_get_expr(&mut self, expr: &Get) -> R;
+    fn visit_get_index_expr(&mut self, expr: &GetIndex) -> R;
     fn visit_grouping_expr(&mut self, expr: &Grouping) -> R;
+    fn visit_interpolation_expr(&mut self, expr: &Interpolation) -> R;
+    fn visit_list_expr(&mut self, expr: &List) -> R;
     fn visit_literal_expr(&mut self, expr: &Literal) -> R;
+    fn visit_logical_expr(&mut self, expr: &Logical) -> R;
+    fn visit_map_expr(&mut self, expr: &Map) -> R;
+    fn visit_set_expr(&mut self, expr: &Set) -> R;
+    fn visit_set_index_expr(&mut self, expr: &SetIndex) -> R;
+    fn visit_super_expr(&mut self, expr: &Super) -> R;
+    fn visit_this_expr(&mut self, expr: &This) -> R;
     fn visit_unary_expr(&mut self, expr: &Unary) -> R;
     fn visit_variable_expr(&mut self, expr: &Variable) -> R;
 }
@@ -55,8 +149,19 @@ impl Expr {
         match self {
             Expr::Assign(expr) => visitor.visit_assign_expr(expr),
             Expr::Binary(expr) => visitor.visit_binary_expr(expr),
+            Expr::Call(expr) => visitor.visit_call_expr(expr),
+            Expr::Get(expr) => visitor.visit_get_expr(expr),
+            Expr::GetIndex(expr) => visitor.visit_get_index_expr(expr),
             Expr::Grouping(expr) => visitor.visit_grouping_expr(expr),
+            Expr::Interpolation(expr) => visitor.visit_interpolation_expr(expr),
+            Expr::List(expr) => visitor.visit_list_expr(expr),
             Expr::Literal(expr) => visitor.visit_literal_expr(expr),
+            Expr::Logical(expr) => visitor.visit_logical_expr(expr),
+            Expr::Map(expr) => visitor.visit_map_expr(expr),
+            Expr::Set(expr) => visitor.visit_set_expr(expr),
+            Expr::SetIndex(expr) => visitor.visit_set_index_expr(expr),
+            Expr::Super(expr) => visitor.visit_super_expr(expr),
+            Expr::This(expr) => visitor.visit_this_expr(expr),
             Expr::Unary(expr) => visitor.visit_unary_expr(expr),
             Expr::Variable(expr) => visitor.visit_variable_expr(expr),
         }
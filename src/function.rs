@@ -0,0 +1,88 @@
+use std::fmt;
+use std::fmt::{Debug, Display, Formatter};
+use std::rc::Rc;
+use crate::class::InstanceRef;
+use crate::environment::EnvRef;
+use crate::error::RuntimeError;
+use crate::interpreter::{Interpreter, Unwind};
+use crate::stmt::Function;
+use crate::symbol::Symbol;
+use crate::token_type::LiteralValue;
+
+pub trait LoxCallable {
+    fn arity(&self) -> usize;
+    fn call(self: Rc<Self>, interpreter: &mut Interpreter, arguments: Vec<LiteralValue>) -> Result<LiteralValue, RuntimeError>;
+}
+
+pub struct LoxFunction {
+    declaration: Rc<Function>,
+    closure: EnvRef,
+    is_initializer: bool,
+}
+
+impl LoxFunction {
+    pub fn new(declaration: Rc<Function>, closure: EnvRef, is_initializer: bool) -> LoxFunction {
+        LoxFunction {
+            declaration,
+            closure,
+            is_initializer,
+        }
+    }
+
+    pub fn closure(&self) -> EnvRef {
+        self.closure
+    }
+
+    pub fn bind(&self, interpreter: &mut Interpreter, instance: InstanceRef) -> LoxFunction {
+        let environment = interpreter.alloc_environment(Some(self.closure));
+        environment.define(interpreter.heap_mut(), Symbol::intern("this"), LiteralValue::Instance(instance));
+
+        LoxFunction::new(Rc::clone(&self.declaration), environment, self.is_initializer)
+    }
+
+    // An initializer always hands back the instance it was bound to.
+    fn bound_this(&self, interpreter: &Interpreter) -> Result<LiteralValue, RuntimeError> {
+        self.closure.get_at(interpreter.heap(), 0, &Symbol::intern("this")).ok_or_else(|| {
+            RuntimeError::new(self.declaration.name.clone(), "Initializer is not bound to an instance.".to_string())
+        })
+    }
+}
+
+impl LoxCallable for LoxFunction {
+    fn arity(&self) -> usize {
+        self.declaration.params.len()
+    }
+
+    fn call(self: Rc<Self>, interpreter: &mut Interpreter, arguments: Vec<LiteralValue>) -> Result<LiteralValue, RuntimeError> {
+        let environment = interpreter.alloc_environment(Some(self.closure));
+
+        for (param, argument) in self.declaration.params.iter().zip(arguments) {
+            environment.define(interpreter.heap_mut(), param.lexeme.clone(), argument);
+        }
+
+        let value = match interpreter.execute_block(&self.declaration.body, environment) {
+            Ok(()) => LiteralValue::Nil,
+            Err(Unwind::Return(value)) => value,
+            Err(Unwind::Error(err)) => return Err(err),
+        };
+
+        if self.is_initializer {
+            return self.bound_this(interpreter);
+        }
+
+        Ok(value)
+    }
+}
+
+impl Display for LoxFunction {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "<fn {}>", self.declaration.name.lexeme)
+    }
+}
+
+// The closure can reach this function again, so only the name is printed.
+impl Debug for LoxFunction {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        Display::fmt(self, f)
+    }
+}
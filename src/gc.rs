@@ -0,0 +1,194 @@
+use std::collections::HashSet;
+use std::fmt::{Display, Formatter};
+
+// Implemented by everything that can hold a reference into a `Heap`, both the
+// objects stored in it and the values the interpreters keep as roots.
+pub trait Trace {
+    fn trace(&self, tracer: &mut Tracer);
+}
+
+// Objects living on a heap also report a rough size so the collector knows
+// when enough memory has been allocated to be worth a collection.
+pub trait Allocation: Trace {
+    fn size(&self) -> usize;
+}
+
+// Collects the slots reachable from the roots, the heap then traces whatever
+// those objects point to in turn.
+#[derive(Default)]
+pub struct Tracer {
+    gray: Vec<usize>,
+    // Addresses of the objects off the heap that have been traced already.
+    visited: HashSet<usize>,
+}
+
+impl Tracer {
+    pub fn new() -> Tracer {
+        Tracer::default()
+    }
+
+    pub fn mark(&mut self, slot: usize) {
+        self.gray.push(slot);
+    }
+
+    // Objects off the heap, like reference counted classes, can be reached
+    // from many places but only need to be traced once.
+    pub fn first_visit<T>(&mut self, object: &T) -> bool {
+        self.visited.insert(object as *const T as usize)
+    }
+}
+
+// A collection runs once the bytes allocated since the last one exceed the
+// threshold, which then grows with the amount of memory still in use.
+#[derive(Clone, Copy, Debug)]
+pub struct GcConfig {
+    pub threshold: usize,
+    pub growth_factor: f64,
+}
+
+impl Default for GcConfig {
+    fn default() -> Self {
+        GcConfig {
+            threshold: 1024 * 1024,
+            growth_factor: 2.0,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct GcStats {
+    pub collections: usize,
+    pub objects_freed: usize,
+    pub bytes_freed: usize,
+    pub bytes_live: usize,
+}
+
+impl Display for GcStats {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "[gc] {} collections, {} bytes freed in {} objects, {} bytes live",
+            self.collections, self.bytes_freed, self.objects_freed, self.bytes_live
+        )
+    }
+}
+
+// An arena of objects addressed by slot with a mark-and-sweep collector.
+// Freed slots are reused by later allocations.
+//
+// Objects are charged for their size when allocated and again whenever they
+// have been handed out mutably, so growing a list or a scope counts too.
+pub struct Heap<T> {
+    slots: Vec<Option<T>>,
+    marks: Vec<bool>,
+    // What each object weighed when it was last measured.
+    sizes: Vec<usize>,
+    // Objects that may have changed size since then.
+    changed: Vec<usize>,
+    free: Vec<usize>,
+    bytes_allocated: usize,
+    next_gc: usize,
+    config: GcConfig,
+    stats: GcStats,
+}
+
+impl<T: Allocation> Heap<T> {
+    pub fn new(config: GcConfig) -> Heap<T> {
+        Heap {
+            slots: Vec::new(),
+            marks: Vec::new(),
+            sizes: Vec::new(),
+            changed: Vec::new(),
+            free: Vec::new(),
+            bytes_allocated: 0,
+            next_gc: config.threshold,
+            config,
+            stats: GcStats::default(),
+        }
+    }
+
+    pub fn alloc(&mut self, object: T) -> usize {
+        let size = object.size();
+        self.bytes_allocated += size;
+
+        match self.free.pop() {
+            Some(slot) => {
+                self.slots[slot] = Some(object);
+                self.sizes[slot] = size;
+                slot
+            }
+            None => {
+                self.slots.push(Some(object));
+                self.marks.push(false);
+                self.sizes.push(size);
+                self.slots.len() - 1
+            }
+        }
+    }
+
+    pub fn get(&self, slot: usize) -> &T {
+        self.slots[slot].as_ref().expect("use of a collected object")
+    }
+
+    pub fn get_mut(&mut self, slot: usize) -> &mut T {
+        if !self.changed.contains(&slot) {
+            self.changed.push(slot);
+        }
+        self.slots[slot].as_mut().expect("use of a collected object")
+    }
+
+    pub fn should_collect(&mut self) -> bool {
+        self.remeasure();
+        self.bytes_allocated > self.next_gc
+    }
+
+    pub fn stats(&self) -> GcStats {
+        let bytes_live = self.changed.iter().fold(self.bytes_allocated, |live, &slot| {
+            self.slots[slot].as_ref().map_or(live, |object| live + object.size() - self.sizes[slot])
+        });
+
+        GcStats { bytes_live, ..self.stats }
+    }
+
+    // Charges the objects that changed for however much they grew or shrank.
+    fn remeasure(&mut self) {
+        for slot in std::mem::take(&mut self.changed) {
+            if let Some(object) = &self.slots[slot] {
+                let size = object.size();
+                self.bytes_allocated = self.bytes_allocated + size - self.sizes[slot];
+                self.sizes[slot] = size;
+            }
+        }
+    }
+
+    // Frees everything not reachable from the slots marked by `tracer`.
+    pub fn collect(&mut self, mut tracer: Tracer) {
+        self.remeasure();
+
+        while let Some(slot) = tracer.gray.pop() {
+            if self.marks[slot] {
+                continue;
+            }
+            self.marks[slot] = true;
+
+            if let Some(object) = &self.slots[slot] {
+                object.trace(&mut tracer);
+            }
+        }
+
+        for (slot, mark) in self.marks.iter_mut().enumerate() {
+            if !std::mem::take(mark) && self.slots[slot].take().is_some() {
+                self.stats.objects_freed += 1;
+                self.stats.bytes_freed += self.sizes[slot];
+                self.bytes_allocated -= self.sizes[slot];
+                self.free.push(slot);
+            }
+        }
+
+        self.stats.collections += 1;
+        self.next_gc = self
+            .config
+            .threshold
+            .max((self.bytes_allocated as f64 * self.config.growth_factor) as usize);
+    }
+}
@@ -0,0 +1,166 @@
+use std::collections::HashMap;
+use std::mem::size_of;
+use crate::gc::{self, Allocation, GcConfig, GcStats, Trace, Tracer};
+use crate::map::{LoxMap, MapKey};
+use crate::native::NativeFunction;
+use crate::symbol::Symbol;
+use crate::vm::Vm;
+
+// A handle to an object living on the VM heap.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ObjRef(usize);
+
+#[derive(Clone, Copy, Debug)]
+pub enum Value {
+    Nil,
+    Bool(bool),
+    Number(f64),
+    Obj(ObjRef),
+}
+
+pub enum Obj {
+    String(Symbol),
+    Closure(Closure),
+    Upvalue(Upvalue),
+    Class(Class),
+    Instance(Instance),
+    BoundMethod(BoundMethod),
+    List(Vec<Value>),
+    Map(LoxMap<Value>),
+    Native(NativeFunction<Vm>),
+}
+
+// `function` indexes into the functions of the running program.
+pub struct Closure {
+    pub function: usize,
+    pub upvalues: Vec<ObjRef>,
+}
+
+// A captured variable points into the stack until the variable goes out of
+// scope, from then on it owns the value.
+pub enum Upvalue {
+    Open(usize),
+    Closed(Value),
+}
+
+pub struct Class {
+    pub name: String,
+    pub methods: HashMap<Symbol, ObjRef>,
+}
+
+pub struct Instance {
+    pub class: ObjRef,
+    pub fields: HashMap<Symbol, Value>,
+}
+
+pub struct BoundMethod {
+    pub receiver: Value,
+    pub method: ObjRef,
+}
+
+pub struct Heap {
+    objects: gc::Heap<Obj>,
+}
+
+impl Heap {
+    pub fn new(config: GcConfig) -> Heap {
+        Heap {
+            objects: gc::Heap::new(config),
+        }
+    }
+
+    pub fn alloc(&mut self, obj: Obj) -> ObjRef {
+        ObjRef(self.objects.alloc(obj))
+    }
+
+    pub fn get(&self, obj: ObjRef) -> &Obj {
+        self.objects.get(obj.0)
+    }
+
+    pub fn get_mut(&mut self, obj: ObjRef) -> &mut Obj {
+        self.objects.get_mut(obj.0)
+    }
+
+    pub fn should_collect(&mut self) -> bool {
+        self.objects.should_collect()
+    }
+
+    pub fn collect(&mut self, tracer: Tracer) {
+        self.objects.collect(tracer);
+    }
+
+    pub fn stats(&self) -> GcStats {
+        self.objects.stats()
+    }
+}
+
+impl Trace for ObjRef {
+    fn trace(&self, tracer: &mut Tracer) {
+        tracer.mark(self.0);
+    }
+}
+
+impl Trace for Value {
+    fn trace(&self, tracer: &mut Tracer) {
+        if let Value::Obj(obj) = self {
+            obj.trace(tracer);
+        }
+    }
+}
+
+impl Trace for Obj {
+    fn trace(&self, tracer: &mut Tracer) {
+        match self {
+            Obj::String(_) | Obj::Native(_) => {}
+            Obj::Closure(closure) => {
+                for upvalue in &closure.upvalues {
+                    upvalue.trace(tracer);
+                }
+            }
+            Obj::Upvalue(Upvalue::Open(_)) => {}
+            Obj::Upvalue(Upvalue::Closed(value)) => value.trace(tracer),
+            Obj::Class(class) => {
+                for method in class.methods.values() {
+                    method.trace(tracer);
+                }
+            }
+            Obj::Instance(instance) => {
+                instance.class.trace(tracer);
+                for value in instance.fields.values() {
+                    value.trace(tracer);
+                }
+            }
+            Obj::BoundMethod(bound) => {
+                bound.receiver.trace(tracer);
+                bound.method.trace(tracer);
+            }
+            Obj::List(elements) => {
+                for element in elements {
+                    element.trace(tracer);
+                }
+            }
+            Obj::Map(entries) => {
+                for value in entries.values() {
+                    value.trace(tracer);
+                }
+            }
+        }
+    }
+}
+
+impl Allocation for Obj {
+    fn size(&self) -> usize {
+        let entry = size_of::<Symbol>() + size_of::<Value>();
+
+        size_of::<Obj>()
+            + match self {
+                Obj::String(string) => string.len(),
+                Obj::Closure(closure) => closure.upvalues.len() * size_of::<ObjRef>(),
+                Obj::Class(class) => class.methods.keys().map(|name| entry + name.len()).sum(),
+                Obj::Instance(instance) => instance.fields.keys().map(|name| entry + name.len()).sum(),
+                Obj::List(elements) => elements.len() * size_of::<Value>(),
+                Obj::Map(entries) => entries.len() * (size_of::<MapKey>() + size_of::<Value>()),
+                Obj::Upvalue(_) | Obj::BoundMethod(_) | Obj::Native(_) => 0,
+            }
+    }
+}
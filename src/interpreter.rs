@@ -1,198 +1,780 @@
-use std::cell::{Ref, RefCell};
+use std::collections::HashMap;
 use std::rc::Rc;
-use crate::environment::Environment;
-use crate::expr::{Assign, Binary, Expr, Grouping, Literal, Unary, Variable, Visitor as ExprVisitor};
-use crate::stmt::{Block, Expression, Print, Stmt, Var, Visitor as StmtVisitor};
+use crate::environment::{EnvRef, Environment};
+use crate::error::RuntimeError;
+use crate::class::{InstanceRef, LoxClass, LoxInstance};
+use crate::expr::{Assign, Binary, Call, Expr, ExprId, Get, GetIndex, Grouping, Interpolation, List, Literal, Logical, Map, Set, SetIndex, Super, This, Unary, Variable, Visitor as ExprVisitor};
+use crate::function::{LoxCallable, LoxFunction};
+use crate::gc::{GcConfig, GcStats, Trace, Tracer};
+use crate::list::{ListMethod, ListRef};
+use crate::map::{LoxMap, MapMethod, MapRef};
+use crate::math::{self, Rng};
+use crate::native::{self, Runtime, ValueType};
+use crate::object::{Heap, Object};
+use crate::string;
+use crate::stmt::{Block, Class, Expression, Function, If, Print, Return, Stmt, Var, While, Visitor as StmtVisitor};
+use crate::symbol::Symbol;
+use crate::token::Token;
 use crate::token_type::{LiteralValue, TokenType};
 use crate::util::Utils;
+use crate::vm::FRAMES_MAX;
 
 pub struct Interpreter {
-    environment: Rc<RefCell<Environment>>
+    heap: Heap,
+    globals: EnvRef,
+    environment: EnvRef,
+    locals: HashMap<ExprId, usize>,
+    // Roots the collector can't find through the current environment: the
+    // environments of the blocks and calls we are nested in, and values held
+    // while the rest of an expression is evaluated.
+    suspended: Vec<EnvRef>,
+    temporaries: Vec<LiteralValue>,
+    // Lox functions being called, counted like the VM's call frames.
+    depth: usize,
+    rng: Rng,
 }
 
-impl ExprVisitor<Result<LiteralValue, String>> for Interpreter {
-    fn visit_assign_expr(&mut self, expr: &Assign) -> Result<LiteralValue, String> {
+// Executing a statement stops early either on a runtime error or on a `return`
+// that has to unwind every enclosing block up to the function call.
+pub enum Unwind {
+    Error(RuntimeError),
+    Return(LiteralValue),
+}
+
+impl From<RuntimeError> for Unwind {
+    fn from(err: RuntimeError) -> Self {
+        Unwind::Error(err)
+    }
+}
+
+impl ExprVisitor<Result<LiteralValue, RuntimeError>> for Interpreter {
+    fn visit_assign_expr(&mut self, expr: &Assign) -> Result<LiteralValue, RuntimeError> {
         let value = self.evaluate(&expr.value)?;
-        self.environment.borrow_mut().assign(expr.clone().name, value.clone()).expect("TODO: panic message");
+
+        match self.locals.get(&expr.id) {
+            Some(distance) => self.environment.assign_at(&mut self.heap, *distance, expr.name.clone(), value.clone())?,
+            None => self.globals.assign(&mut self.heap, expr.name.clone(), value.clone())?,
+        }
 
         Ok(value)
     }
 
-    fn visit_binary_expr(&mut self, expr: &Binary) -> Result<LiteralValue, String> {
-        let left = self.evaluate(&expr.clone().left);
-        let right = self.evaluate(&expr.clone().right);
-
-        match left? {
-            LiteralValue::String(left) => match right? {
-                LiteralValue::String(right) => match expr.operator.token_type {
-                    TokenType::Plus => Ok(LiteralValue::String(format!("{}{}", left, right))),
-                    TokenType::EqualEqual => Ok(LiteralValue::Bool(left == right)),
-                    TokenType::BangEqual => Ok(LiteralValue::Bool(left != right)),
-                    _ => Err("Invalid operator".to_string()),
-                },
-                LiteralValue::Number(left) => Interpreter::str_int_equality(expr),
-                _ => Err("Invalid operator".to_string()),
-            },
-            LiteralValue::Number(left) => match right? {
-                LiteralValue::Number(right) => match expr.operator.token_type {
-                    TokenType::Minus => Ok(LiteralValue::Number(left - right)),
-                    TokenType::Star => Ok(LiteralValue::Number(left * right)),
-                    TokenType::Slash => Ok(LiteralValue::Number(left / right)),
-                    TokenType::Plus => Ok(LiteralValue::Number(left + right)),
-                    TokenType::Greater => Ok(LiteralValue::Bool(left > right)),
-                    TokenType::GreaterEqual => Ok(LiteralValue::Bool(left >= right)),
-                    TokenType::Less => Ok(LiteralValue::Bool(left < right)),
-                    TokenType::LessEqual => Ok(LiteralValue::Bool(left <= right)),
-                    TokenType::EqualEqual => Ok(LiteralValue::Bool(left == right)),
-                    TokenType::BangEqual => Ok(LiteralValue::Bool(left != right)),
-                    _ => Err("Invalid operator".to_string()),
-                },
-                LiteralValue::String(left) => Interpreter::str_int_equality(expr),
-                _ => Err("Invalid operator".to_string()),
-            },
-            LiteralValue::Bool(left) => match right? {
-                LiteralValue::Bool(right) => match expr.operator.token_type {
-                    TokenType::EqualEqual => Ok(LiteralValue::Bool(left == right)),
-                    TokenType::BangEqual => Ok(LiteralValue::Bool(left != right)),
-                    _ => Err("Invalid operator".to_string()),
-                },
-                _ => Err("Invalid operator".to_string()),
+    fn visit_binary_expr(&mut self, expr: &Binary) -> Result<LiteralValue, RuntimeError> {
+        let left = self.evaluate(&expr.left)?;
+        let right = self.with_root(&left, |interpreter| interpreter.evaluate(&expr.right))?;
+
+        match expr.operator.token_type {
+            TokenType::EqualEqual => return Ok(LiteralValue::Bool(Interpreter::is_equal(&left, &right))),
+            TokenType::BangEqual => return Ok(LiteralValue::Bool(!Interpreter::is_equal(&left, &right))),
+            _ => {}
+        }
+
+        match (left, right) {
+            (LiteralValue::Number(left), LiteralValue::Number(right)) => match expr.operator.token_type {
+                TokenType::Minus => Ok(LiteralValue::Number(left - right)),
+                TokenType::Star => Ok(LiteralValue::Number(left * right)),
+                TokenType::Slash => Ok(LiteralValue::Number(left / right)),
+                TokenType::Plus => Ok(LiteralValue::Number(left + right)),
+                TokenType::Greater => Ok(LiteralValue::Bool(left > right)),
+                TokenType::GreaterEqual => Ok(LiteralValue::Bool(left >= right)),
+                TokenType::Less => Ok(LiteralValue::Bool(left < right)),
+                TokenType::LessEqual => Ok(LiteralValue::Bool(left <= right)),
+                _ => Err(Interpreter::invalid_operator(expr)),
             },
-            LiteralValue::Nil => match right? {
-                LiteralValue::Nil => Ok(LiteralValue::Bool(true)),
-                _ => Ok(LiteralValue::Bool(false)),
+            (LiteralValue::String(left), LiteralValue::String(right)) if expr.operator.token_type == TokenType::Plus => {
+                Ok(LiteralValue::String(Symbol::from(format!("{}{}", left, right))))
+            }
+            _ => Err(Interpreter::invalid_operator(expr)),
+        }
+    }
+
+    fn visit_call_expr(&mut self, expr: &Call) -> Result<LiteralValue, RuntimeError> {
+        // List and map methods aren't values of their own, they only exist
+        // as calls.
+        let callee = match &*expr.callee {
+            Expr::Get(get) => match self.evaluate(&get.object)? {
+                LiteralValue::List(list) => {
+                    return self.with_root(&LiteralValue::List(list), |interpreter| interpreter.call_list_method(expr, list, &get.name));
+                }
+                LiteralValue::Map(map) => {
+                    return self.with_root(&LiteralValue::Map(map), |interpreter| interpreter.call_map_method(expr, map, &get.name));
+                }
+                receiver @ LiteralValue::String(_) => {
+                    return self.with_root(&receiver, |interpreter| interpreter.call_string_method(expr, receiver.clone(), &get.name));
+                }
+                object => self.property(object, &get.name)?,
             },
+            callee => self.evaluate(callee)?,
+        };
+
+        self.with_root(&callee, |interpreter| interpreter.call(expr, callee.clone()))
+    }
+
+    fn visit_get_expr(&mut self, expr: &Get) -> Result<LiteralValue, RuntimeError> {
+        let object = self.evaluate(&expr.object)?;
+        self.property(object, &expr.name)
+    }
+
+    fn visit_grouping_expr(&mut self, expr: &Grouping) -> Result<LiteralValue, RuntimeError> {
+        self.evaluate(&expr.expression)
+    }
+
+    fn visit_get_index_expr(&mut self, expr: &GetIndex) -> Result<LiteralValue, RuntimeError> {
+        let object = self.evaluate(&expr.object)?;
+        let index = self.with_root(&object, |interpreter| interpreter.evaluate(&expr.index))?;
+
+        match object {
+            LiteralValue::List(list) => list.get(&self.heap, &expr.bracket, &index),
+            LiteralValue::Map(map) => map.get(&self.heap, &expr.bracket, &index),
+            LiteralValue::String(string) => string::char_at(&string, self.number(&index))
+                .map(|c| LiteralValue::String(Symbol::from(c.to_string())))
+                .map_err(|message| RuntimeError::new(expr.bracket.clone(), message)),
+            _ => Err(Interpreter::not_indexable(&expr.bracket)),
         }
     }
 
-    fn visit_grouping_expr(&mut self, expr: &Grouping) -> Result<LiteralValue, String> {
-        Ok(self.evaluate(&expr.expression)?)
+    // Every part is turned into text the same way `print` would show it.
+    fn visit_interpolation_expr(&mut self, expr: &Interpolation) -> Result<LiteralValue, RuntimeError> {
+        let mut string = String::new();
+        for part in &expr.parts {
+            let part = self.evaluate(part)?;
+            string.push_str(&self.stringify(&part));
+        }
+
+        Ok(LiteralValue::String(Symbol::from(string)))
     }
 
-    fn visit_literal_expr(&mut self, expr: &Literal) -> Result<LiteralValue, String> {
+    fn visit_list_expr(&mut self, expr: &List) -> Result<LiteralValue, RuntimeError> {
+        // Elements evaluated so far must survive a collection triggered by a later one.
+        let depth = self.temporaries.len();
+        let elements = expr
+            .elements
+            .iter()
+            .map(|element| {
+                let value = self.evaluate(element)?;
+                self.root(&value);
+                Ok(value)
+            })
+            .collect::<Result<Vec<_>, RuntimeError>>();
+        self.temporaries.truncate(depth);
+
+        Ok(LiteralValue::List(self.alloc_list(elements?)))
+    }
+
+    fn visit_literal_expr(&mut self, expr: &Literal) -> Result<LiteralValue, RuntimeError> {
         Ok(expr.clone().value)
     }
 
-    fn visit_unary_expr(&mut self, expr: &Unary) -> Result<LiteralValue, String> {
+    fn visit_logical_expr(&mut self, expr: &Logical) -> Result<LiteralValue, RuntimeError> {
+        let left = self.evaluate(&expr.left)?;
+
+        // Short-circuit and hand back the operand that decided the result.
+        if expr.operator.token_type == TokenType::Or {
+            if Interpreter::is_truthy(&left) {
+                return Ok(left);
+            }
+        } else if !Interpreter::is_truthy(&left) {
+            return Ok(left);
+        }
+
+        self.evaluate(&expr.right)
+    }
+
+    fn visit_map_expr(&mut self, expr: &Map) -> Result<LiteralValue, RuntimeError> {
+        // Values evaluated so far must survive a collection triggered by a later one.
+        let depth = self.temporaries.len();
+        let mut entries = LoxMap::new();
+        let result = expr.entries.iter().try_for_each(|(key, value)| {
+            let key = self.evaluate(key)?;
+            let key = MapRef::key(&expr.brace, &key)?;
+            let value = self.evaluate(value)?;
+            self.root(&value);
+            entries.insert(key, value);
+            Ok(())
+        });
+        self.temporaries.truncate(depth);
+        result?;
+
+        Ok(LiteralValue::Map(MapRef::new(self.heap.alloc(Object::Map(entries)))))
+    }
+
+    fn visit_set_expr(&mut self, expr: &Set) -> Result<LiteralValue, RuntimeError> {
+        let object = self.evaluate(&expr.object)?;
+        let instance = match &object {
+            LiteralValue::Instance(instance) => instance,
+            _ => return Err(RuntimeError::new(expr.name.clone(), "Only instances have fields.".to_string())),
+        };
+
+        let value = self.with_root(&object, |interpreter| interpreter.evaluate(&expr.value))?;
+        instance.set(&mut self.heap, &expr.name, value.clone());
+
+        Ok(value)
+    }
+
+    fn visit_set_index_expr(&mut self, expr: &SetIndex) -> Result<LiteralValue, RuntimeError> {
+        let object = self.evaluate(&expr.object)?;
+        let (index, value) = self.with_root(&object, |interpreter| {
+            let index = interpreter.evaluate(&expr.index)?;
+            let value = interpreter.evaluate(&expr.value)?;
+            Ok::<_, RuntimeError>((index, value))
+        })?;
+
+        match object {
+            LiteralValue::List(list) => list.set(&mut self.heap, &expr.bracket, &index, value.clone())?,
+            LiteralValue::Map(map) => map.set(&mut self.heap, &expr.bracket, &index, value.clone())?,
+            LiteralValue::String(_) => return Err(RuntimeError::new(expr.bracket.clone(), "Strings are immutable.".to_string())),
+            _ => return Err(Interpreter::not_indexable(&expr.bracket)),
+        }
+
+        Ok(value)
+    }
+
+    fn visit_super_expr(&mut self, expr: &Super) -> Result<LiteralValue, RuntimeError> {
+        let distance = match self.locals.get(&expr.id) {
+            Some(distance) => *distance,
+            None => return Err(RuntimeError::new(expr.keyword.clone(), "Can't use 'super' outside of a method.".to_string())),
+        };
+
+        let superclass = match self.environment.get_at(&self.heap, distance, &Symbol::intern("super")) {
+            Some(LiteralValue::Class(class)) => class,
+            _ => return Err(RuntimeError::new(expr.keyword.clone(), "Superclass must be a class.".to_string())),
+        };
+
+        // `this` is always bound in the scope just inside the one holding `super`.
+        let instance = match self.environment.get_at(&self.heap, distance - 1, &Symbol::intern("this")) {
+            Some(LiteralValue::Instance(instance)) => instance,
+            _ => return Err(RuntimeError::new(expr.keyword.clone(), "Can't use 'super' outside of a method.".to_string())),
+        };
+
+        match superclass.find_method(&expr.method.lexeme) {
+            Some(method) => Ok(LiteralValue::Function(Rc::new(method.bind(self, instance)))),
+            None => Err(RuntimeError::new(expr.method.clone(), format!("Undefined property '{}'.", expr.method.lexeme))),
+        }
+    }
+
+    fn visit_this_expr(&mut self, expr: &This) -> Result<LiteralValue, RuntimeError> {
+        self.look_up_variable(expr.id, &expr.keyword)
+    }
+
+    fn visit_unary_expr(&mut self, expr: &Unary) -> Result<LiteralValue, RuntimeError> {
         let right = self.evaluate(&expr.right);
 
         match expr.operator.token_type {
             TokenType::Minus => match right? {
                 LiteralValue::Number(n) => Ok(LiteralValue::Number(-n)),
-                _ => {
-                    eprintln!("Tried negating a non number");
-                    Err("Tried negating a non number".to_string())
-                }
+                _ => Err(RuntimeError::new(expr.operator.clone(), "Tried negating a non number".to_string())),
             },
-            TokenType::Bang => Ok(Interpreter::is_truthy(right?)),
-            _ => {
-                eprintln!("Tried to evaluate a non-unary operator in the unary Visitor");
-                Err("Tried to evaluate a non-unary operator in the unary Visitor".to_string())
-            }
+            TokenType::Bang => Ok(LiteralValue::Bool(!Interpreter::is_truthy(&right?))),
+            _ => Err(RuntimeError::new(
+                expr.operator.clone(),
+                "Tried to evaluate a non-unary operator in the unary Visitor".to_string(),
+            )),
         }
     }
 
-    fn visit_variable_expr(&mut self, expr: &Variable) -> Result<LiteralValue, String> {
-        self.environment.borrow().get(expr.clone().name)
+    fn visit_variable_expr(&mut self, expr: &Variable) -> Result<LiteralValue, RuntimeError> {
+        self.look_up_variable(expr.id, &expr.name)
     }
 }
 
-impl StmtVisitor<()> for Interpreter {
-    fn visit_block_stmt(&mut self, stmt: &Block) -> () {
-        self.execute_block(&stmt.statements, Rc::new(RefCell::from(Environment::local(self.environment.clone())))).expect("TODO: panic message");
+impl StmtVisitor<Result<(), Unwind>> for Interpreter {
+    fn visit_block_stmt(&mut self, stmt: &Block) -> Result<(), Unwind> {
+        let environment = self.alloc_environment(Some(self.environment));
+        self.execute_block(&stmt.statements, environment)
     }
 
-    fn visit_expression_stmt(&mut self, stmt: &Expression) {
-        self.evaluate(&stmt.expression).unwrap_or_else(|_| {
-            eprintln!("Tried executing an expression which is not an expression");
-            std::process::exit(70)
-        });
+    fn visit_class_stmt(&mut self, stmt: &Class) -> Result<(), Unwind> {
+        let superclass = match &stmt.superclass {
+            Some(superclass) => match self.visit_variable_expr(superclass)? {
+                LiteralValue::Class(class) => Some(class),
+                _ => return Err(RuntimeError::new(superclass.name.clone(), "Superclass must be a class.".to_string()).into()),
+            },
+            None => None,
+        };
+
+        self.environment.define(&mut self.heap, stmt.name.lexeme.clone(), LiteralValue::Nil);
+
+        // Methods of a subclass close over an extra scope that holds `super`.
+        let closure = match &superclass {
+            Some(superclass) => {
+                let environment = self.alloc_environment(Some(self.environment));
+                environment.define(&mut self.heap, Symbol::intern("super"), LiteralValue::Class(Rc::clone(superclass)));
+                environment
+            }
+            None => self.environment,
+        };
+
+        let mut methods = HashMap::new();
+        for method in &stmt.methods {
+            let function = LoxFunction::new(Rc::clone(method), closure, method.name.lexeme == "init");
+            methods.insert(method.name.lexeme.clone(), Rc::new(function));
+        }
+
+        let class = LoxClass::new(stmt.name.lexeme.to_string(), superclass, methods);
+        self.environment.assign(&mut self.heap, stmt.name.clone(), LiteralValue::Class(Rc::new(class)))?;
+        Ok(())
+    }
+
+    fn visit_expression_stmt(&mut self, stmt: &Expression) -> Result<(), Unwind> {
+        self.evaluate(&stmt.expression)?;
+        Ok(())
+    }
+
+    fn visit_function_stmt(&mut self, stmt: &Rc<Function>) -> Result<(), Unwind> {
+        let function = LoxFunction::new(Rc::clone(stmt), self.environment, false);
+        self.environment
+            .define(&mut self.heap, stmt.name.lexeme.clone(), LiteralValue::Function(Rc::new(function)));
+        Ok(())
+    }
+
+    fn visit_if_stmt(&mut self, stmt: &If) -> Result<(), Unwind> {
+        if Interpreter::is_truthy(&self.evaluate(&stmt.condition)?) {
+            self.execute(&stmt.then_branch)
+        } else if let Some(else_branch) = &stmt.else_branch {
+            self.execute(else_branch)
+        } else {
+            Ok(())
+        }
     }
 
-    fn visit_print_stmt(&mut self, stmt: &Print) {
-        let val = Utils::print_literal(&self.evaluate(&stmt.expression).unwrap_or_else(|_| {
-            eprintln!("Tried executing an expression which is not an expression");
-            std::process::exit(70)
-        }));
+    fn visit_print_stmt(&mut self, stmt: &Print) -> Result<(), Unwind> {
+        let value = self.evaluate(&stmt.expression)?;
 
-        println!("{}", val);
+        println!("{}", self.stringify(&value));
+        Ok(())
     }
 
-    fn visit_var_stmt(&mut self, stmt: &Var) -> () {
+    fn visit_return_stmt(&mut self, stmt: &Return) -> Result<(), Unwind> {
+        let value = match &stmt.value {
+            Some(value) => self.evaluate(value)?,
+            None => LiteralValue::Nil,
+        };
+
+        Err(Unwind::Return(value))
+    }
+
+    fn visit_var_stmt(&mut self, stmt: &Var) -> Result<(), Unwind> {
         let mut val = LiteralValue::Nil;
 
-        if let Some(expr) = stmt.clone().initializer {
-            val = self.evaluate(&Box::from(expr)).unwrap_or_else(|_| {
-                eprintln!("Tried executing an expression which is not an expression");
-                std::process::exit(70)
-            });
+        if let Some(expr) = &stmt.initializer {
+            val = self.evaluate(expr)?;
+        }
+
+        self.environment.define(&mut self.heap, stmt.name.lexeme.clone(), val);
+        Ok(())
+    }
+
+    fn visit_while_stmt(&mut self, stmt: &While) -> Result<(), Unwind> {
+        while Interpreter::is_truthy(&self.evaluate(&stmt.condition)?) {
+            self.execute(&stmt.body)?;
         }
+        Ok(())
+    }
+}
 
-        self.environment.borrow_mut().define(stmt.name.lexeme.clone(), val);
+impl Default for Interpreter {
+    fn default() -> Self {
+        Interpreter::new()
     }
 }
 
 impl Interpreter {
     pub fn new() -> Interpreter {
+        Interpreter::with_gc(GcConfig::default())
+    }
+
+    pub fn with_gc(config: GcConfig) -> Interpreter {
+        let mut heap = Heap::new(config);
+        let globals = EnvRef::new(heap.alloc(Object::Environment(Environment::new(None))));
+        for native in native::library::<Interpreter>() {
+            globals.define(&mut heap, Symbol::intern(native.name), LiteralValue::Native(Rc::new(native)));
+        }
+        for (name, value) in math::CONSTANTS {
+            globals.define(&mut heap, Symbol::intern(name), LiteralValue::Number(value));
+        }
+
         Interpreter {
-            environment: Rc::new(RefCell::from(Environment::global()))
+            heap,
+            globals,
+            environment: globals,
+            locals: HashMap::new(),
+            suspended: Vec::new(),
+            temporaries: Vec::new(),
+            depth: 0,
+            rng: Rng::default(),
         }
     }
 
-    pub fn interpret(&mut self, stmts: &Vec<Stmt>) -> Result<(), String> {
+    pub fn globals(&self) -> &Environment {
+        self.heap.environment(self.globals)
+    }
+
+    pub fn heap(&self) -> &Heap {
+        &self.heap
+    }
+
+    pub fn heap_mut(&mut self) -> &mut Heap {
+        &mut self.heap
+    }
+
+    pub fn gc_stats(&self) -> GcStats {
+        self.heap.stats()
+    }
+
+    pub fn alloc_environment(&mut self, enclosing: Option<EnvRef>) -> EnvRef {
+        EnvRef::new(self.heap.alloc(Object::Environment(Environment::new(enclosing))))
+    }
+
+    pub fn alloc_instance(&mut self, class: Rc<LoxClass>) -> InstanceRef {
+        InstanceRef::new(class, self.heap.alloc(Object::Instance(LoxInstance::default())))
+    }
+
+    pub fn alloc_list(&mut self, elements: Vec<LiteralValue>) -> ListRef {
+        ListRef::new(self.heap.alloc(Object::List(elements)))
+    }
+
+    // How `print` shows a value. Strings inside a list or map are quoted and
+    // one that contains itself is cut short.
+    pub fn stringify(&self, value: &LiteralValue) -> String {
+        self.stringify_nested(value, &mut Vec::new())
+    }
+
+    // `open` holds the heap slots of the lists and maps being printed.
+    fn stringify_nested(&self, value: &LiteralValue, open: &mut Vec<usize>) -> String {
+        match value {
+            LiteralValue::List(list) if open.contains(&list.slot()) => "[...]".to_string(),
+            LiteralValue::List(list) => {
+                open.push(list.slot());
+                let elements: Vec<String> = self
+                    .heap
+                    .list(*list)
+                    .iter()
+                    .map(|element| self.stringify_element(element, open))
+                    .collect();
+                open.pop();
+
+                format!("[{}]", elements.join(", "))
+            }
+            LiteralValue::Map(map) if open.contains(&map.slot()) => "{...}".to_string(),
+            LiteralValue::Map(map) => {
+                open.push(map.slot());
+                let entries: Vec<String> = self
+                    .heap
+                    .map(*map)
+                    .iter()
+                    .map(|(key, value)| format!("{}: {}", key, self.stringify_element(value, open)))
+                    .collect();
+                open.pop();
+
+                format!("{{{}}}", entries.join(", "))
+            }
+            value => Utils::print_literal(value),
+        }
+    }
+
+    fn stringify_element(&self, element: &LiteralValue, open: &mut Vec<usize>) -> String {
+        match element {
+            LiteralValue::String(string) => format!("{:?}", string),
+            element => self.stringify_nested(element, open),
+        }
+    }
+
+    pub fn resolve(&mut self, id: ExprId, depth: usize) {
+        self.locals.insert(id, depth);
+    }
+
+    pub fn interpret(&mut self, stmts: &[Stmt]) -> Result<(), RuntimeError> {
         for stmt in stmts {
-            self.execute(stmt);
+            match self.execute(stmt) {
+                Ok(()) => {}
+                Err(Unwind::Error(err)) => return Err(err),
+                // The resolver rejects `return` outside of a function body.
+                Err(Unwind::Return(_)) => return Ok(()),
+            }
         }
         Ok(())
     }
 
-    pub fn interpret_expression(&mut self, expr: &Box<Expr>) -> Result<LiteralValue, String> {
+    pub fn interpret_expression(&mut self, expr: &Expr) -> Result<LiteralValue, RuntimeError> {
         self.evaluate(expr)
     }
 
-    fn execute_block(&mut self, stmts: &Vec<Box<Stmt>>, environment: Rc<RefCell<Environment>>) -> Result<(), String> {
-        let previous = Rc::clone(&self.environment);
-
+    pub fn execute_block(&mut self, stmts: &[Stmt], environment: EnvRef) -> Result<(), Unwind> {
+        self.suspended.push(self.environment);
         self.environment = environment;
 
-        for stmt in stmts {
-            self.execute(stmt);
+        // The previous environment has to be restored even when a statement fails.
+        let result = stmts.iter().try_for_each(|stmt| self.execute(stmt));
+
+        self.environment = self.suspended.pop().expect("suspended environment");
+        result
+    }
+
+    // Statement boundaries are the only points where every live value is
+    // reachable from a root, so that is where the collector runs.
+    fn execute(&mut self, stmt: &Stmt) -> Result<(), Unwind> {
+        if self.heap.should_collect() {
+            self.collect_garbage();
+        }
+
+        stmt.accept(self)
+    }
+
+    fn collect_garbage(&mut self) {
+        let mut tracer = Tracer::new();
+
+        self.globals.trace(&mut tracer);
+        self.environment.trace(&mut tracer);
+        for environment in &self.suspended {
+            environment.trace(&mut tracer);
+        }
+        for value in &self.temporaries {
+            value.trace(&mut tracer);
+        }
+
+        self.heap.collect(tracer);
+    }
+
+    // Keeps `value` alive while `f` runs, along with anything `f` roots itself.
+    fn with_root<R>(&mut self, value: &LiteralValue, f: impl FnOnce(&mut Interpreter) -> R) -> R {
+        let depth = self.temporaries.len();
+        self.root(value);
+
+        let result = f(self);
+        self.temporaries.truncate(depth);
+        result
+    }
+
+    // Only functions, classes, instances, lists and maps can reach the heap.
+    fn root(&mut self, value: &LiteralValue) {
+        if matches!(
+            value,
+            LiteralValue::Function(_)
+                | LiteralValue::Class(_)
+                | LiteralValue::Instance(_)
+                | LiteralValue::List(_)
+                | LiteralValue::Map(_)
+        ) {
+            self.temporaries.push(value.clone());
         }
+    }
+
+    fn call(&mut self, expr: &Call, callee: LiteralValue) -> Result<LiteralValue, RuntimeError> {
+        let arguments = self.arguments(expr)?;
 
-        self.environment = previous;
+        // A class only runs Lox code when it has an initializer.
+        let (callable, runs_function): (Rc<dyn LoxCallable>, bool) = match callee {
+            LiteralValue::Function(function) => (function, true),
+            LiteralValue::Class(class) => {
+                let has_initializer = class.find_method(&Symbol::intern("init")).is_some();
+                (class, has_initializer)
+            }
+            LiteralValue::Native(native) => {
+                Interpreter::check_arity(&expr.paren, native.arity, arguments.len())?;
+                return native
+                    .call(self, &arguments)
+                    .map_err(|err| RuntimeError::new(expr.paren.clone(), err.to_string()));
+            }
+            _ => return Err(RuntimeError::new(expr.paren.clone(), "Can only call functions and classes.".to_string())),
+        };
+
+        Interpreter::check_arity(&expr.paren, callable.arity(), arguments.len())?;
+        if !runs_function {
+            return callable.call(self, arguments);
+        }
+
+        if self.depth + 1 >= FRAMES_MAX {
+            return Err(RuntimeError::new(expr.paren.clone(), "Stack overflow.".to_string()));
+        }
+        self.depth += 1;
+        let result = callable.call(self, arguments);
+        self.depth -= 1;
+        result
+    }
+
+    // Rooted until the caller's `with_root` scope ends.
+    fn arguments(&mut self, expr: &Call) -> Result<Vec<LiteralValue>, RuntimeError> {
+        let mut arguments = Vec::new();
+        for argument in &expr.arguments {
+            let value = self.evaluate(argument)?;
+            self.root(&value);
+            arguments.push(value);
+        }
+        Ok(arguments)
+    }
+
+    fn call_list_method(&mut self, expr: &Call, list: ListRef, name: &Token) -> Result<LiteralValue, RuntimeError> {
+        let method = ListMethod::find(&name.lexeme)
+            .ok_or_else(|| RuntimeError::new(name.clone(), format!("Undefined property '{}'.", name.lexeme)))?;
+        let arguments = self.arguments(expr)?;
+        Interpreter::check_arity(&expr.paren, method.arity(), arguments.len())?;
+
+        match method {
+            ListMethod::Push => {
+                self.heap.list_mut(list).extend(arguments);
+                Ok(LiteralValue::Nil)
+            }
+        }
+    }
+
+    fn call_map_method(&mut self, expr: &Call, map: MapRef, name: &Token) -> Result<LiteralValue, RuntimeError> {
+        let method = MapMethod::find(&name.lexeme)
+            .ok_or_else(|| RuntimeError::new(name.clone(), format!("Undefined property '{}'.", name.lexeme)))?;
+        let arguments = self.arguments(expr)?;
+        Interpreter::check_arity(&expr.paren, method.arity(), arguments.len())?;
+
+        match method {
+            MapMethod::Keys => {
+                let keys = self.heap.map(map).keys().map(|key| key.to_literal()).collect();
+                Ok(LiteralValue::List(self.alloc_list(keys)))
+            }
+            MapMethod::Values => {
+                let values = self.heap.map(map).values().cloned().collect();
+                Ok(LiteralValue::List(self.alloc_list(values)))
+            }
+            MapMethod::Has => {
+                let key = MapRef::key(&expr.paren, &arguments[0])?;
+                Ok(LiteralValue::Bool(self.heap.map(map).contains_key(&key)))
+            }
+        }
+    }
+
+    // The string goes in front of the arguments.
+    fn call_string_method(&mut self, expr: &Call, receiver: LiteralValue, name: &Token) -> Result<LiteralValue, RuntimeError> {
+        let method = string::method::<Interpreter>(&name.lexeme)
+            .ok_or_else(|| RuntimeError::new(name.clone(), format!("Undefined property '{}'.", name.lexeme)))?;
+
+        let mut arguments = vec![receiver];
+        arguments.extend(self.arguments(expr)?);
+        Interpreter::check_arity(&expr.paren, method.arity, arguments.len() - 1)?;
+
+        method
+            .call(self, &arguments)
+            .map_err(|err| RuntimeError::new(expr.paren.clone(), err.to_string()))
+    }
+
+    fn check_arity(paren: &Token, arity: usize, count: usize) -> Result<(), RuntimeError> {
+        if count != arity {
+            return Err(RuntimeError::new(paren.clone(), format!("Expected {} arguments but got {}.", arity, count)));
+        }
         Ok(())
     }
 
-    fn execute(&mut self, stmt: &Stmt)  {
-        stmt.accept(self)
+    fn property(&mut self, object: LiteralValue, name: &Token) -> Result<LiteralValue, RuntimeError> {
+        match object {
+            LiteralValue::Instance(instance) => instance.get(self, name),
+            _ => Err(RuntimeError::new(name.clone(), "Only instances have properties.".to_string())),
+        }
     }
 
-    fn evaluate(&mut self, expr: &Box<Expr>) -> Result<LiteralValue, String> {
+    fn not_indexable(bracket: &Token) -> RuntimeError {
+        RuntimeError::new(bracket.clone(), "Only lists, maps and strings can be indexed.".to_string())
+    }
+
+    fn evaluate(&mut self, expr: &Expr) -> Result<LiteralValue, RuntimeError> {
         expr.accept(self)
     }
 
-    fn str_int_equality(expr: &Binary) -> Result<LiteralValue, String> {
-        match expr.operator.token_type {
-            TokenType::EqualEqual => Ok(LiteralValue::Bool(false)),
-            TokenType::BangEqual => Ok(LiteralValue::Bool(true)),
-            _ => Err("Invalid operator".to_string()),
+    fn look_up_variable(&self, id: ExprId, name: &Token) -> Result<LiteralValue, RuntimeError> {
+        match self.locals.get(&id) {
+            Some(distance) => self
+                .environment
+                .get_at(&self.heap, *distance, &name.lexeme)
+                .ok_or_else(|| RuntimeError::new(name.clone(), format!("Undefined variable '{}'.", name.lexeme))),
+            None => self.globals.get(&self.heap, name.clone()),
         }
     }
 
-    fn is_truthy(val: LiteralValue) -> LiteralValue {
+    fn invalid_operator(expr: &Binary) -> RuntimeError {
+        RuntimeError::new(expr.operator.clone(), "Invalid operator".to_string())
+    }
+
+    // Values of different types are never equal, objects compare by identity.
+    // Strings are interned, so comparing them is a pointer compare too.
+    fn is_equal(left: &LiteralValue, right: &LiteralValue) -> bool {
+        match (left, right) {
+            (LiteralValue::Nil, LiteralValue::Nil) => true,
+            (LiteralValue::Bool(left), LiteralValue::Bool(right)) => left == right,
+            (LiteralValue::Number(left), LiteralValue::Number(right)) => left == right,
+            (LiteralValue::String(left), LiteralValue::String(right)) => left == right,
+            (LiteralValue::Function(left), LiteralValue::Function(right)) => Rc::ptr_eq(left, right),
+            (LiteralValue::Native(left), LiteralValue::Native(right)) => Rc::ptr_eq(left, right),
+            (LiteralValue::Class(left), LiteralValue::Class(right)) => Rc::ptr_eq(left, right),
+            (LiteralValue::Instance(left), LiteralValue::Instance(right)) => left == right,
+            (LiteralValue::List(left), LiteralValue::List(right)) => left == right,
+            (LiteralValue::Map(left), LiteralValue::Map(right)) => left == right,
+            _ => false,
+        }
+    }
+
+    // Lox only treats `nil` and `false` as falsey, everything else is truthy.
+    fn is_truthy(val: &LiteralValue) -> bool {
         match val {
-            LiteralValue::Number(n) => {
-                if n == 0.0 {
-                    LiteralValue::Bool(true)
-                } else {
-                    LiteralValue::Bool(false)
-                }
-            }
-            LiteralValue::Bool(b) => LiteralValue::Bool(!b),
-            LiteralValue::Nil => LiteralValue::Bool(true),
-            LiteralValue::String(_) => {
-                panic!("Tried banging a string lmao")
-            }
+            LiteralValue::Bool(b) => *b,
+            LiteralValue::Nil => false,
+            _ => true,
+        }
+    }
+}
+
+impl Runtime for Interpreter {
+    type Value = LiteralValue;
+
+    fn value_type(&self, value: &LiteralValue) -> ValueType {
+        match value {
+            LiteralValue::Nil => ValueType::Nil,
+            LiteralValue::Bool(_) => ValueType::Bool,
+            LiteralValue::Number(_) => ValueType::Number,
+            LiteralValue::String(_) => ValueType::String,
+            LiteralValue::List(_) => ValueType::List,
+            LiteralValue::Map(_) => ValueType::Map,
+            LiteralValue::Function(_) | LiteralValue::Native(_) => ValueType::Function,
+            LiteralValue::Class(_) => ValueType::Class,
+            LiteralValue::Instance(_) => ValueType::Instance,
+        }
+    }
+
+    fn number(&self, value: &LiteralValue) -> Option<f64> {
+        match value {
+            LiteralValue::Number(number) => Some(*number),
+            _ => None,
+        }
+    }
+
+    fn string<'a>(&'a self, value: &'a LiteralValue) -> Option<&'a str> {
+        match value {
+            LiteralValue::String(string) => Some(string.as_str()),
+            _ => None,
+        }
+    }
+
+    fn list<'a>(&'a self, value: &'a LiteralValue) -> Option<&'a [LiteralValue]> {
+        match value {
+            LiteralValue::List(list) => Some(self.heap.list(*list)),
+            _ => None,
         }
     }
+
+    fn map_len(&self, value: &LiteralValue) -> Option<usize> {
+        match value {
+            LiteralValue::Map(map) => Some(self.heap.map(*map).len()),
+            _ => None,
+        }
+    }
+
+    fn stringify(&self, value: &LiteralValue) -> String {
+        Interpreter::stringify(self, value)
+    }
+
+    fn literal(&mut self, value: LiteralValue) -> LiteralValue {
+        value
+    }
+
+    fn alloc_list(&mut self, elements: Vec<LiteralValue>) -> LiteralValue {
+        LiteralValue::List(Interpreter::alloc_list(self, elements))
+    }
+
+    fn rng(&mut self) -> &mut Rng {
+        &mut self.rng
+    }
 }
@@ -0,0 +1,30 @@
+pub mod ast_printer;
+pub mod bytecode;
+pub mod chunk;
+pub mod class;
+pub mod compiler;
+pub mod diagnostic;
+pub mod disassembler;
+pub mod environment;
+pub mod error;
+pub mod expr;
+pub mod function;
+pub mod gc;
+pub mod heap;
+pub mod interpreter;
+pub mod list;
+pub mod map;
+pub mod math;
+pub mod native;
+pub mod object;
+pub mod parser;
+pub mod repl;
+pub mod resolver;
+pub mod scanner;
+pub mod stmt;
+pub mod string;
+pub mod symbol;
+pub mod token;
+pub mod token_type;
+pub mod util;
+pub mod vm;
@@ -0,0 +1,67 @@
+use crate::error::RuntimeError;
+use crate::object::Heap;
+use crate::token::Token;
+use crate::token_type::LiteralValue;
+
+// The built-in methods every list has, like the ones of maps.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ListMethod {
+    Push,
+}
+
+impl ListMethod {
+    pub fn find(name: &str) -> Option<ListMethod> {
+        match name {
+            "push" => Some(ListMethod::Push),
+            _ => None,
+        }
+    }
+
+    pub fn arity(self) -> usize {
+        match self {
+            ListMethod::Push => 1,
+        }
+    }
+}
+
+// A handle to the elements of a list on the interpreter's heap. Every copy
+// of a list value shares them, so a change is visible through all aliases.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ListRef(usize);
+
+impl ListRef {
+    pub fn new(slot: usize) -> ListRef {
+        ListRef(slot)
+    }
+
+    pub fn slot(self) -> usize {
+        self.0
+    }
+
+    pub fn get(self, heap: &Heap, bracket: &Token, index: &LiteralValue) -> Result<LiteralValue, RuntimeError> {
+        let elements = heap.list(self);
+        let index = ListRef::index(bracket, index, elements.len())?;
+        Ok(elements[index].clone())
+    }
+
+    pub fn set(self, heap: &mut Heap, bracket: &Token, index: &LiteralValue, value: LiteralValue) -> Result<(), RuntimeError> {
+        let elements = heap.list_mut(self);
+        let index = ListRef::index(bracket, index, elements.len())?;
+        elements[index] = value;
+        Ok(())
+    }
+
+    fn index(bracket: &Token, index: &LiteralValue, len: usize) -> Result<usize, RuntimeError> {
+        match index {
+            LiteralValue::Number(number) if number.fract() == 0.0 => {
+                if *number >= 0.0 && (*number as usize) < len {
+                    Ok(*number as usize)
+                } else {
+                    let message = format!("List index {} out of bounds for length {}.", number, len);
+                    Err(RuntimeError::new(bracket.clone(), message))
+                }
+            }
+            _ => Err(RuntimeError::new(bracket.clone(), "List index must be an integer.".to_string())),
+        }
+    }
+}
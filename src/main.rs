@@ -1,81 +1,159 @@
-mod ast_printer;
-mod error;
-mod expr;
-mod interpreter;
-mod parser;
-mod scanner;
-mod token;
-mod token_type;
-mod stmt;
-mod util;
-mod environment;
-
-use crate::ast_printer::AstPrinter;
-use crate::expr::{Binary, Expr, Grouping, Literal, Unary};
-use crate::interpreter::Interpreter;
-use crate::token::Token;
-use crate::token_type::LiteralValue;
-use parser::Parser;
-use scanner::Scanner;
+use codecrafters_interpreter::ast_printer::AstPrinter;
+use codecrafters_interpreter::bytecode;
+use codecrafters_interpreter::compiler::Compiler;
+use codecrafters_interpreter::diagnostic::Diagnostic;
+use codecrafters_interpreter::disassembler::Disassembler;
+use codecrafters_interpreter::error::LoxError;
+use codecrafters_interpreter::expr::Expr;
+use codecrafters_interpreter::gc::{GcConfig, GcStats};
+use codecrafters_interpreter::interpreter::Interpreter;
+use codecrafters_interpreter::parser::Parser;
+use codecrafters_interpreter::repl::Repl;
+use codecrafters_interpreter::resolver::Resolver;
+use codecrafters_interpreter::scanner::Scanner;
+use codecrafters_interpreter::stmt::Stmt;
+use codecrafters_interpreter::token_type::LiteralValue;
+use codecrafters_interpreter::vm::Vm;
 use std::env;
-use std::fs;
-use std::io::{self, Write};
-use crate::util::Utils;
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use std::io::{self, BufRead, BufReader, IsTerminal, Read};
+use std::thread;
+
+// The tree-walker recurses on the native stack. Nesting as many calls as the
+// VM allows takes about 50 MiB in a debug build, this leaves room to spare.
+const STACK_SIZE: usize = 128 * 1024 * 1024;
 
 fn main() {
-    let args: Vec<String> = env::args().collect();
+    if !runs_tree_walker() {
+        return cli();
+    }
+
+    match thread::Builder::new().stack_size(STACK_SIZE).spawn(cli) {
+        Ok(cli) => {
+            if cli.join().is_err() {
+                std::process::exit(101);
+            }
+        }
+        // Deep recursion may overflow the default stack, but everything else still works.
+        Err(_) => cli(),
+    }
+}
+
+// Only the tree-walker needs the bigger stack, the VM keeps its frames on the heap.
+fn runs_tree_walker() -> bool {
+    let (flags, args): (Vec<String>, Vec<String>) = env::args().partition(|arg| arg.starts_with("--"));
+    match args.get(1).map(String::as_str) {
+        None | Some("repl") | Some("evaluate") => true,
+        Some("run") => !flags.iter().any(|flag| flag == "--backend=vm"),
+        _ => false,
+    }
+}
+
+fn cli() {
+    let (flags, args): (Vec<String>, Vec<String>) = env::args().partition(|arg| arg.starts_with("--"));
+    let gc = gc_config(&flags);
+    let gc_stats = flags.iter().any(|flag| flag == "--gc-stats");
+
+    if args.len() == 1 || args[1] == "repl" {
+        let mut repl = Repl::with_gc(gc);
+        let result = repl.run();
+        if gc_stats {
+            eprintln!("{}", repl.gc_stats());
+        }
+        if let Err(err) = result {
+            eprintln!("REPL failed: {}", err);
+            std::process::exit(74);
+        }
+        return;
+    }
+
     if args.len() < 3 {
-        writeln!(io::stderr(), "Usage: {} tokenize <filename>", args[0]).unwrap();
+        eprintln!("Usage: {} tokenize <filename>|- [--pretty-errors] [--error-columns]", args[0]);
+        eprintln!("       {} run <filename>|- [--backend=tree|vm] [--gc-stats] [--gc-threshold=<bytes>] [--gc-growth=<factor>]", args[0]);
+        eprintln!("       {} disassemble <filename>", args[0]);
+        eprintln!("       {} compile <filename> -o <output>", args[0]);
+        eprintln!("       {} repl", args[0]);
         return;
     }
 
     let command = &args[1];
     let filename = &args[2];
 
-    match command.as_str() {
-        "tokenize" => {
-            // You can use print statements as follows for debugging, they'll be visible when running tests.
-            writeln!(io::stderr(), "Logs from your program will appear here!").unwrap();
+    let explicit_backend = flags.iter().find_map(|flag| flag.strip_prefix("--backend="));
+    let backend = explicit_backend.unwrap_or("tree");
+    if backend != "tree" && backend != "vm" {
+        eprintln!("Unknown backend: {}, expected tree or vm", backend);
+        std::process::exit(64);
+    }
 
-            let file_contents = fs::read_to_string(filename).unwrap_or_else(|_| {
-                writeln!(io::stderr(), "Failed to read file {}", filename).unwrap();
-                String::new()
-            });
+    let pretty = flags.iter().any(|flag| flag == "--pretty-errors");
+    let columns = flags.iter().any(|flag| flag == "--error-columns");
 
-            // Uncomment this block to pass the first stage
-            if !file_contents.is_empty() {
-                let mut scanner = Scanner::new(file_contents);
-                scanner.scan_tokens();
+    // `-` reads the program from standard input. Source is scanned as it is
+    // read either way, only compiled programs are loaded whole.
+    let (mut input, name): (Box<dyn BufRead>, &str) = if filename == "-" {
+        (Box::new(io::stdin().lock()), "<stdin>")
+    } else {
+        let file = File::open(filename).unwrap_or_else(|err| read_failed(filename, err));
+        (Box::new(BufReader::new(file)), filename)
+    };
+    let head = input.fill_buf().unwrap_or_else(|err| read_failed(name, err));
+    let empty = head.is_empty();
 
-                print!("{}", scanner);
+    // Compiled programs skip the front end and always run on the VM.
+    if (command == "run" || command == "disassemble") && bytecode::is_compiled(head) {
+        if command == "run" && explicit_backend == Some("tree") {
+            eprintln!("{} is compiled and can only run with --backend=vm", name);
+            std::process::exit(64);
+        }
 
-                if scanner.has_error() {
-                    std::process::exit(65);
-                }
-            } else {
-                println!("EOF  null");
-            }
+        let mut contents = Vec::new();
+        input.read_to_end(&mut contents).unwrap_or_else(|err| read_failed(name, err));
+        let program = bytecode::decode(&contents).unwrap_or_else(|err| {
+            eprintln!("Can't load {}: {}", name, err);
+            std::process::exit(65);
+        });
+        if command == "disassemble" {
+            print!("{}", Disassembler::new(&program).disassemble());
+            return;
         }
-        "parse" => {
-            let file_contents = fs::read_to_string(filename).unwrap_or_else(|_| {
-                writeln!(io::stderr(), "Failed to read file {}", filename).unwrap();
-                String::new()
-            });
 
-            if !file_contents.is_empty() {
-                let mut scanner = Scanner::new(file_contents);
-                scanner.scan_tokens();
+        let reporter = Reporter {
+            pretty,
+            columns,
+            filename: name.to_string(),
+            gc,
+            gc_stats,
+        };
+        let mut vm = Vm::with_gc(program, gc);
+        let result = vm.run();
+        reporter.print_gc_stats(vm.gc_stats());
+        result.unwrap_or_else(|err| reporter.report([err]));
+        return;
+    }
 
-                if scanner.has_error() {
-                    std::process::exit(65);
-                }
+    let reporter = Reporter {
+        pretty,
+        columns,
+        filename: name.to_string(),
+        gc,
+        gc_stats,
+    };
+    let scanner = Scanner::from_reader(input);
 
-                let mut expr = Parser::new(scanner.get_tokens());
+    match command.as_str() {
+        "tokenize" => {
+            // You can use print statements as follows for debugging, they'll be visible when running tests.
+            eprintln!("Logs from your program will appear here!");
+
+            // Uncomment this block to pass the first stage
+            tokenize(scanner, &reporter);
+        }
+        "parse" => {
+            if !empty {
                 let mut ast_printer = AstPrinter {};
-                let expression = expr.expression().unwrap_or_else(|err| {
-                    eprintln!("Parsing error: {}", err);
-                    std::process::exit(65);
-                });
+                let expression = reporter.expression(scanner);
 
                 println!("{}", ast_printer.print(expression));
             } else {
@@ -83,70 +161,199 @@ fn main() {
             }
         }
         "evaluate" => {
-            let file_contents = fs::read_to_string(filename).unwrap_or_else(|_| {
-                writeln!(io::stderr(), "Failed to read file {}", filename).unwrap();
-                String::new()
-            });
+            if !empty {
+                let expression = reporter.expression(scanner);
+
+                let mut interpreter = Interpreter::with_gc(gc);
+                let value = interpreter
+                    .interpret_expression(&expression)
+                    .unwrap_or_else(|err| reporter.report([err]));
+                let literal_value = interpreter.stringify(&value);
+
+                println!("{}", literal_value);
+            }
+        }
+        "run" => {
+            if !empty {
+                let (statements, mut interpreter) = reporter.front_end(scanner);
 
-            if !file_contents.is_empty() {
-                let mut scanner = Scanner::new(file_contents);
-                scanner.scan_tokens();
+                match backend {
+                    "vm" => {
+                        let program = Compiler::new().compile(&statements).unwrap_or_else(|err| reporter.report([err]));
 
-                if scanner.has_error() {
-                    std::process::exit(65);
+                        let mut vm = Vm::with_gc(program, gc);
+                        let result = vm.run();
+                        reporter.print_gc_stats(vm.gc_stats());
+                        result.unwrap_or_else(|err| reporter.report([err]))
+                    }
+                    _ => {
+                        let result = interpreter.interpret(&statements);
+                        reporter.print_gc_stats(interpreter.gc_stats());
+                        result.unwrap_or_else(|err| reporter.report([err]))
+                    }
                 }
+            }
+        }
+        "disassemble" => {
+            if !empty {
+                let (statements, _) = reporter.front_end(scanner);
 
-                let mut expr = Parser::new(scanner.get_tokens());
+                let program = Compiler::new().compile(&statements).unwrap_or_else(|err| reporter.report([err]));
+                print!("{}", Disassembler::new(&program).disassemble());
+            }
+        }
+        "compile" => {
+            let output = args
+                .iter()
+                .position(|arg| arg == "-o")
+                .and_then(|index| args.get(index + 1))
+                .map_or_else(|| Path::new(filename).with_extension("loxc"), PathBuf::from);
 
-                let expression = expr.expression().unwrap_or_else(|err| {
-                    eprintln!("Parsing error: {}", err);
-                    std::process::exit(65);
-                });;
+            let (statements, _) = reporter.front_end(scanner);
+            let program = Compiler::new().compile(&statements).unwrap_or_else(|err| reporter.report([err]));
 
-                let mut interpreter = Interpreter::new();
-                let literal_value = Utils::print_literal(&interpreter
-                    .interpret_expression(&Box::from(expression))
-                    .unwrap_or_else(|err| {
-                        eprintln!("Runtime error: {}", err);
-                        std::process::exit(70);
-                    }));
+            if let Err(err) = fs::write(&output, bytecode::encode(&program)) {
+                eprintln!("Failed to write {}: {}", output.display(), err);
+                std::process::exit(74);
+            }
+        }
+        _ => {
+            eprintln!("Unknown command: {}", command);
+        }
+    }
+}
 
-                println!("{}", literal_value);
+// Tokens are printed as they are scanned, errors once the whole input is done.
+fn tokenize(scanner: Scanner, reporter: &Reporter) {
+    let mut errors = Vec::new();
+    for result in scanner {
+        match result {
+            Ok(token) => {
+                let literal = match &token.literal {
+                    // Escaped like in source so every token stays on one line.
+                    Some(LiteralValue::String(string)) => {
+                        let quoted = format!("{:?}", string.as_str());
+                        quoted[1..quoted.len() - 1].to_string()
+                    }
+                    Some(literal) => literal.to_string(),
+                    None => "null".to_string(),
+                };
+                println!("{} {} {}", token.token_type, token.lexeme, literal);
             }
+            Err(err) => errors.push(err),
         }
-        "run" => {
-            let file_contents = fs::read_to_string(filename).unwrap_or_else(|_| {
-                writeln!(io::stderr(), "Failed to read file {}", filename).unwrap();
-                String::new()
-            });
+    }
 
-            if !file_contents.is_empty() {
-                let mut scanner = Scanner::new(file_contents);
-                scanner.scan_tokens();
+    if !errors.is_empty() {
+        reporter.report(errors);
+    }
+}
 
-                if scanner.has_error() {
-                    std::process::exit(65);
+fn read_failed(name: &str, err: io::Error) -> ! {
+    eprintln!("Failed to read file {}: {}", name, err);
+    std::process::exit(66);
+}
+
+// Reads the collector settings, exiting with a usage error on bad values.
+fn gc_config(flags: &[String]) -> GcConfig {
+    let mut config = GcConfig::default();
+
+    for flag in flags {
+        if let Some(threshold) = flag.strip_prefix("--gc-threshold=") {
+            config.threshold = threshold.parse().unwrap_or_else(|_| {
+                eprintln!("Invalid GC threshold: {}, expected a number of bytes", threshold);
+                std::process::exit(64);
+            });
+        } else if let Some(growth) = flag.strip_prefix("--gc-growth=") {
+            config.growth_factor = match growth.parse::<f64>() {
+                Ok(factor) if factor >= 0.0 => factor,
+                _ => {
+                    eprintln!("Invalid GC growth factor: {}, expected a non-negative number", growth);
+                    std::process::exit(64);
                 }
+            };
+        }
+    }
 
-                let mut expr = Parser::new(scanner.get_tokens());
+    config
+}
 
-                let expression = expr.parse().unwrap_or_else(|err| {
-                    eprintln!("Parsing error: {}", err);
-                    std::process::exit(65);
-                });
+struct Reporter {
+    pretty: bool,
+    columns: bool,
+    filename: String,
+    gc: GcConfig,
+    gc_stats: bool,
+}
 
-                let mut interpreter = Interpreter::new();
-                interpreter
-                    .interpret(&Box::from(expression))
-                    .unwrap_or_else(|err| {
-                        eprintln!("Runtime error: {}", err);
-                        std::process::exit(70);
-                    })
-            }
+impl Reporter {
+    // Scans, parses and resolves a whole program, stopping at the first stage with errors.
+    fn front_end(&self, scanner: Scanner) -> (Vec<Stmt>, Interpreter) {
+        let mut parser = Parser::new(scanner);
+        let (statements, errors) = parser.parse();
+
+        if !parser.scan_errors().is_empty() {
+            self.report(parser.scan_errors().to_vec());
         }
-        _ => {
-            writeln!(io::stderr(), "Unknown command: {}", command).unwrap();
-            return;
+        if !errors.is_empty() {
+            self.report(errors);
+        }
+
+        let mut interpreter = Interpreter::with_gc(self.gc);
+        Resolver::new(&mut interpreter)
+            .resolve_program(&statements)
+            .unwrap_or_else(|errors| self.report(errors));
+
+        (statements, interpreter)
+    }
+
+    // The `parse` and `evaluate` commands take a single expression. The rest
+    // of the input is still scanned so any scan errors in it are reported.
+    fn expression(&self, mut scanner: Scanner) -> Expr {
+        let mut parser = Parser::new(&mut scanner);
+        let result = parser.parse_expression();
+        let mut scan_errors = parser.scan_errors().to_vec();
+        scan_errors.extend(scanner.filter_map(Result::err));
+
+        if !scan_errors.is_empty() {
+            self.report(scan_errors);
         }
+        result.unwrap_or_else(|errors| self.report(errors))
+    }
+
+    fn print_gc_stats(&self, stats: GcStats) {
+        if self.gc_stats {
+            eprintln!("{}", stats);
+        }
+    }
+
+    // Prints every error and exits with the status code of the stage that failed.
+    fn report<E: Into<LoxError>>(&self, errors: impl IntoIterator<Item = E>) -> ! {
+        let mut exit_code = 65;
+        let color = io::stderr().is_terminal() && env::var_os("NO_COLOR").is_none();
+        // Snippets are taken from the file again. Standard input can't be
+        // read twice, so its source lines are left blank.
+        let source = if self.pretty && self.filename != "<stdin>" {
+            fs::read_to_string(&self.filename).unwrap_or_default()
+        } else {
+            String::new()
+        };
+
+        for err in errors {
+            let err = err.into();
+            if self.pretty {
+                eprint!("{}", Diagnostic::from(&err).render(&source, &self.filename, color));
+            } else {
+                let message = if self.columns { err.located() } else { err.to_string() };
+                match err {
+                    LoxError::Parse(_) => eprintln!("Parsing error: {}", message),
+                    LoxError::Runtime(_) => eprintln!("Runtime error: {}", message),
+                    _ => eprintln!("{}", message),
+                }
+            }
+            exit_code = err.exit_code();
+        }
+
+        std::process::exit(exit_code)
     }
 }
@@ -0,0 +1,182 @@
+use std::collections::HashMap;
+use std::fmt::{self, Display, Formatter};
+use crate::error::RuntimeError;
+use crate::object::Heap;
+use crate::symbol::Symbol;
+use crate::token::Token;
+use crate::token_type::LiteralValue;
+use crate::util::Utils;
+
+// What a map can be keyed by. Keys are equal exactly when the values are
+// `==`, so numbers hash by their bits with -0 folded into 0, and NaN, which
+// is never equal to itself, is rejected.
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub enum MapKey {
+    Nil,
+    Bool(bool),
+    Number(u64),
+    String(Symbol),
+}
+
+impl MapKey {
+    pub fn number(number: f64) -> Result<MapKey, String> {
+        if number.is_nan() {
+            return Err("Map key cannot be NaN.".to_string());
+        }
+
+        let number = if number == 0.0 { 0.0 } else { number };
+        Ok(MapKey::Number(number.to_bits()))
+    }
+
+    pub fn from_literal(value: &LiteralValue) -> Result<MapKey, String> {
+        match value {
+            LiteralValue::Nil => Ok(MapKey::Nil),
+            LiteralValue::Bool(boolean) => Ok(MapKey::Bool(*boolean)),
+            LiteralValue::Number(number) => MapKey::number(*number),
+            LiteralValue::String(string) => Ok(MapKey::String(string.clone())),
+            _ => Err(MapKey::invalid()),
+        }
+    }
+
+    pub fn to_literal(&self) -> LiteralValue {
+        match self {
+            MapKey::Nil => LiteralValue::Nil,
+            MapKey::Bool(boolean) => LiteralValue::Bool(*boolean),
+            MapKey::Number(bits) => LiteralValue::Number(f64::from_bits(*bits)),
+            MapKey::String(string) => LiteralValue::String(string.clone()),
+        }
+    }
+
+    pub fn invalid() -> String {
+        "Map keys must be strings, numbers, booleans or nil.".to_string()
+    }
+}
+
+// Keys print the way they read in a map literal.
+impl Display for MapKey {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            MapKey::String(string) => write!(f, "{:?}", string),
+            key => write!(f, "{}", Utils::print_literal(&key.to_literal())),
+        }
+    }
+}
+
+// The entries of a map, iterated in the order their keys were first added.
+// Both backends store their own kind of value in it.
+pub struct LoxMap<V> {
+    entries: Vec<(MapKey, V)>,
+    index: HashMap<MapKey, usize>,
+}
+
+impl<V> LoxMap<V> {
+    pub fn new() -> LoxMap<V> {
+        LoxMap {
+            entries: Vec::new(),
+            index: HashMap::new(),
+        }
+    }
+
+    pub fn get(&self, key: &MapKey) -> Option<&V> {
+        self.index.get(key).map(|&slot| &self.entries[slot].1)
+    }
+
+    pub fn insert(&mut self, key: MapKey, value: V) {
+        match self.index.get(&key) {
+            Some(&slot) => self.entries[slot].1 = value,
+            None => {
+                self.index.insert(key.clone(), self.entries.len());
+                self.entries.push((key, value));
+            }
+        }
+    }
+
+    pub fn contains_key(&self, key: &MapKey) -> bool {
+        self.index.contains_key(key)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&MapKey, &V)> {
+        self.entries.iter().map(|(key, value)| (key, value))
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &MapKey> {
+        self.entries.iter().map(|(key, _)| key)
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = &V> {
+        self.entries.iter().map(|(_, value)| value)
+    }
+}
+
+impl<V> Default for LoxMap<V> {
+    fn default() -> LoxMap<V> {
+        LoxMap::new()
+    }
+}
+
+// The built-in methods every map has, `map.keys()` and so on.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MapMethod {
+    Keys,
+    Values,
+    Has,
+}
+
+impl MapMethod {
+    pub fn find(name: &str) -> Option<MapMethod> {
+        match name {
+            "keys" => Some(MapMethod::Keys),
+            "values" => Some(MapMethod::Values),
+            "has" => Some(MapMethod::Has),
+            _ => None,
+        }
+    }
+
+    pub fn arity(self) -> usize {
+        match self {
+            MapMethod::Keys | MapMethod::Values => 0,
+            MapMethod::Has => 1,
+        }
+    }
+}
+
+// A handle to the entries of a map on the interpreter's heap, shared by
+// every copy of the map value like a `ListRef`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MapRef(usize);
+
+impl MapRef {
+    pub fn new(slot: usize) -> MapRef {
+        MapRef(slot)
+    }
+
+    pub fn slot(self) -> usize {
+        self.0
+    }
+
+    pub fn get(self, heap: &Heap, bracket: &Token, key: &LiteralValue) -> Result<LiteralValue, RuntimeError> {
+        let key = MapRef::key(bracket, key)?;
+        match heap.map(self).get(&key) {
+            Some(value) => Ok(value.clone()),
+            None => Err(RuntimeError::new(bracket.clone(), format!("Undefined key {}.", key))),
+        }
+    }
+
+    pub fn set(self, heap: &mut Heap, bracket: &Token, key: &LiteralValue, value: LiteralValue) -> Result<(), RuntimeError> {
+        let key = MapRef::key(bracket, key)?;
+        heap.map_mut(self).insert(key, value);
+        Ok(())
+    }
+
+    pub fn key(token: &Token, key: &LiteralValue) -> Result<MapKey, RuntimeError> {
+        MapKey::from_literal(key).map_err(|message| RuntimeError::new(token.clone(), message))
+    }
+}
@@ -0,0 +1,162 @@
+use std::f64::consts;
+use crate::error::NativeError;
+use crate::native::{self, NativeFunction, Runtime};
+use crate::token_type::LiteralValue;
+
+pub const CONSTANTS: [(&str, f64); 2] = [("PI", consts::PI), ("E", consts::E)];
+
+// A splitmix64 generator. Every run starts from the same seed so scripts are
+// reproducible unless they call `seed()`.
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Rng {
+        Rng { state: seed }
+    }
+
+    // Uniform in [0, 1).
+    pub fn next_f64(&mut self) -> f64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^= z >> 31;
+
+        (z >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+impl Default for Rng {
+    fn default() -> Rng {
+        Rng::new(0)
+    }
+}
+
+pub fn natives<R: Runtime>() -> Vec<NativeFunction<R>> {
+    vec![
+        NativeFunction::new("abs", 1, abs),
+        NativeFunction::new("acos", 1, acos),
+        NativeFunction::new("asin", 1, asin),
+        NativeFunction::new("atan", 1, atan),
+        NativeFunction::new("atan2", 2, atan2),
+        NativeFunction::new("ceil", 1, ceil),
+        NativeFunction::new("cos", 1, cos),
+        NativeFunction::new("exp", 1, exp),
+        NativeFunction::new("floor", 1, floor),
+        NativeFunction::new("log", 1, log),
+        NativeFunction::new("log10", 1, log10),
+        NativeFunction::new("max", 2, max),
+        NativeFunction::new("min", 2, min),
+        NativeFunction::new("pow", 2, pow),
+        NativeFunction::new("random", 0, random),
+        NativeFunction::new("round", 1, round),
+        NativeFunction::new("seed", 1, seed),
+        NativeFunction::new("sin", 1, sin),
+        NativeFunction::new("sqrt", 1, sqrt),
+        NativeFunction::new("tan", 1, tan),
+    ]
+}
+
+fn abs<R: Runtime>(runtime: &mut R, arguments: &[R::Value]) -> Result<R::Value, NativeError> {
+    unary(runtime, "abs", arguments, f64::abs)
+}
+
+fn acos<R: Runtime>(runtime: &mut R, arguments: &[R::Value]) -> Result<R::Value, NativeError> {
+    unary(runtime, "acos", arguments, f64::acos)
+}
+
+fn asin<R: Runtime>(runtime: &mut R, arguments: &[R::Value]) -> Result<R::Value, NativeError> {
+    unary(runtime, "asin", arguments, f64::asin)
+}
+
+fn atan<R: Runtime>(runtime: &mut R, arguments: &[R::Value]) -> Result<R::Value, NativeError> {
+    unary(runtime, "atan", arguments, f64::atan)
+}
+
+// Takes y before x, like C's.
+fn atan2<R: Runtime>(runtime: &mut R, arguments: &[R::Value]) -> Result<R::Value, NativeError> {
+    binary(runtime, "atan2", arguments, f64::atan2)
+}
+
+fn ceil<R: Runtime>(runtime: &mut R, arguments: &[R::Value]) -> Result<R::Value, NativeError> {
+    unary(runtime, "ceil", arguments, f64::ceil)
+}
+
+fn cos<R: Runtime>(runtime: &mut R, arguments: &[R::Value]) -> Result<R::Value, NativeError> {
+    unary(runtime, "cos", arguments, f64::cos)
+}
+
+fn exp<R: Runtime>(runtime: &mut R, arguments: &[R::Value]) -> Result<R::Value, NativeError> {
+    unary(runtime, "exp", arguments, f64::exp)
+}
+
+fn floor<R: Runtime>(runtime: &mut R, arguments: &[R::Value]) -> Result<R::Value, NativeError> {
+    unary(runtime, "floor", arguments, f64::floor)
+}
+
+// The natural logarithm.
+fn log<R: Runtime>(runtime: &mut R, arguments: &[R::Value]) -> Result<R::Value, NativeError> {
+    unary(runtime, "log", arguments, f64::ln)
+}
+
+fn log10<R: Runtime>(runtime: &mut R, arguments: &[R::Value]) -> Result<R::Value, NativeError> {
+    unary(runtime, "log10", arguments, f64::log10)
+}
+
+fn max<R: Runtime>(runtime: &mut R, arguments: &[R::Value]) -> Result<R::Value, NativeError> {
+    binary(runtime, "max", arguments, f64::max)
+}
+
+fn min<R: Runtime>(runtime: &mut R, arguments: &[R::Value]) -> Result<R::Value, NativeError> {
+    binary(runtime, "min", arguments, f64::min)
+}
+
+fn pow<R: Runtime>(runtime: &mut R, arguments: &[R::Value]) -> Result<R::Value, NativeError> {
+    binary(runtime, "pow", arguments, f64::powf)
+}
+
+fn random<R: Runtime>(runtime: &mut R, _arguments: &[R::Value]) -> Result<R::Value, NativeError> {
+    let number = runtime.rng().next_f64();
+    Ok(runtime.literal(LiteralValue::Number(number)))
+}
+
+// Halfway cases round away from zero.
+fn round<R: Runtime>(runtime: &mut R, arguments: &[R::Value]) -> Result<R::Value, NativeError> {
+    unary(runtime, "round", arguments, f64::round)
+}
+
+fn seed<R: Runtime>(runtime: &mut R, arguments: &[R::Value]) -> Result<R::Value, NativeError> {
+    let seed = native::number(runtime, "seed", &arguments[0])?;
+    *runtime.rng() = Rng::new(seed.to_bits());
+    Ok(runtime.literal(LiteralValue::Nil))
+}
+
+fn sin<R: Runtime>(runtime: &mut R, arguments: &[R::Value]) -> Result<R::Value, NativeError> {
+    unary(runtime, "sin", arguments, f64::sin)
+}
+
+fn sqrt<R: Runtime>(runtime: &mut R, arguments: &[R::Value]) -> Result<R::Value, NativeError> {
+    unary(runtime, "sqrt", arguments, f64::sqrt)
+}
+
+fn tan<R: Runtime>(runtime: &mut R, arguments: &[R::Value]) -> Result<R::Value, NativeError> {
+    unary(runtime, "tan", arguments, f64::tan)
+}
+
+fn unary<R: Runtime>(runtime: &mut R, function: &'static str, arguments: &[R::Value], f: fn(f64) -> f64) -> Result<R::Value, NativeError> {
+    let x = native::number(runtime, function, &arguments[0])?;
+    Ok(runtime.literal(LiteralValue::Number(f(x))))
+}
+
+fn binary<R: Runtime>(
+    runtime: &mut R,
+    function: &'static str,
+    arguments: &[R::Value],
+    f: fn(f64, f64) -> f64,
+) -> Result<R::Value, NativeError> {
+    let x = native::number(runtime, function, &arguments[0])?;
+    let y = native::number(runtime, function, &arguments[1])?;
+    Ok(runtime.literal(LiteralValue::Number(f(x, y))))
+}
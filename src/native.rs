@@ -0,0 +1,207 @@
+use std::fmt::{self, Debug, Display, Formatter};
+use std::io::{self, BufRead};
+use std::time::{SystemTime, UNIX_EPOCH};
+use crate::error::NativeError;
+use crate::math::{self, Rng};
+use crate::token_type::LiteralValue;
+
+// The type of a value as `type()` reports it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ValueType {
+    Nil,
+    Bool,
+    Number,
+    String,
+    List,
+    Map,
+    Function,
+    Class,
+    Instance,
+}
+
+impl Display for ValueType {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            ValueType::Nil => "nil",
+            ValueType::Bool => "bool",
+            ValueType::Number => "number",
+            ValueType::String => "string",
+            ValueType::List => "list",
+            ValueType::Map => "map",
+            ValueType::Function => "function",
+            ValueType::Class => "class",
+            ValueType::Instance => "instance",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+// What a native needs from the backend running it. The tree-walker and the
+// VM both implement it, so each native is only written once.
+pub trait Runtime: Sized {
+    type Value: Clone;
+
+    fn value_type(&self, value: &Self::Value) -> ValueType;
+    fn number(&self, value: &Self::Value) -> Option<f64>;
+    fn string<'a>(&'a self, value: &'a Self::Value) -> Option<&'a str>;
+    fn list<'a>(&'a self, value: &'a Self::Value) -> Option<&'a [Self::Value]>;
+    fn map_len(&self, value: &Self::Value) -> Option<usize>;
+    // How `print` would show the value.
+    fn stringify(&self, value: &Self::Value) -> String;
+
+    // Only called with nil, booleans, numbers and strings.
+    fn literal(&mut self, value: LiteralValue) -> Self::Value;
+    fn alloc_list(&mut self, elements: Vec<Self::Value>) -> Self::Value;
+
+    // The generator behind `random()`.
+    fn rng(&mut self) -> &mut Rng;
+}
+
+pub type NativeFn<R> = fn(&mut R, &[<R as Runtime>::Value]) -> Result<<R as Runtime>::Value, NativeError>;
+
+// A function implemented in Rust. Callers check the arity, so `function`
+// always gets exactly `arity` arguments.
+pub struct NativeFunction<R: Runtime> {
+    pub name: &'static str,
+    pub arity: usize,
+    function: NativeFn<R>,
+}
+
+impl<R: Runtime> NativeFunction<R> {
+    pub fn new(name: &'static str, arity: usize, function: NativeFn<R>) -> NativeFunction<R> {
+        NativeFunction { name, arity, function }
+    }
+
+    pub fn call(&self, runtime: &mut R, arguments: &[R::Value]) -> Result<R::Value, NativeError> {
+        (self.function)(runtime, arguments)
+    }
+}
+
+impl<R: Runtime> Clone for NativeFunction<R> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<R: Runtime> Copy for NativeFunction<R> {}
+
+impl<R: Runtime> Display for NativeFunction<R> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "<native fn {}>", self.name)
+    }
+}
+
+impl<R: Runtime> Debug for NativeFunction<R> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        Display::fmt(self, f)
+    }
+}
+
+// The natives every program starts with as globals.
+pub fn library<R: Runtime>() -> Vec<NativeFunction<R>> {
+    let mut natives = core();
+    natives.extend(math::natives());
+    natives
+}
+
+fn core<R: Runtime>() -> Vec<NativeFunction<R>> {
+    vec![
+        NativeFunction::new("clock", 0, clock),
+        NativeFunction::new("input", 0, input),
+        NativeFunction::new("len", 1, len),
+        NativeFunction::new("num", 1, num),
+        NativeFunction::new("str", 1, str_of),
+        NativeFunction::new("type", 1, type_of),
+    ]
+}
+
+// Seconds since the Unix epoch.
+fn clock<R: Runtime>(runtime: &mut R, _arguments: &[R::Value]) -> Result<R::Value, NativeError> {
+    let elapsed = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+    Ok(runtime.literal(LiteralValue::Number(elapsed.as_secs_f64())))
+}
+
+// A line from standard input without its line ending, or nil once the input
+// is exhausted.
+fn input<R: Runtime>(runtime: &mut R, _arguments: &[R::Value]) -> Result<R::Value, NativeError> {
+    let mut line = String::new();
+    let read = io::stdin().lock().read_line(&mut line).map_err(|err| NativeError::Io {
+        function: "input",
+        message: err.to_string(),
+    })?;
+
+    if read == 0 {
+        return Ok(runtime.literal(LiteralValue::Nil));
+    }
+
+    let line = line.strip_suffix('\n').unwrap_or(&line);
+    let line = line.strip_suffix('\r').unwrap_or(line);
+    Ok(runtime.literal(LiteralValue::String(line.into())))
+}
+
+// Strings are measured in characters, not bytes.
+fn len<R: Runtime>(runtime: &mut R, arguments: &[R::Value]) -> Result<R::Value, NativeError> {
+    let value = &arguments[0];
+    let len = if let Some(string) = runtime.string(value) {
+        string.chars().count()
+    } else if let Some(elements) = runtime.list(value) {
+        elements.len()
+    } else if let Some(len) = runtime.map_len(value) {
+        len
+    } else {
+        return Err(argument_type(runtime, "len", "a string, list or map", value));
+    };
+
+    Ok(runtime.literal(LiteralValue::Number(len as f64)))
+}
+
+fn num<R: Runtime>(runtime: &mut R, arguments: &[R::Value]) -> Result<R::Value, NativeError> {
+    let value = &arguments[0];
+    if let Some(number) = runtime.number(value) {
+        return Ok(runtime.literal(LiteralValue::Number(number)));
+    }
+
+    let string = match runtime.string(value) {
+        Some(string) => string,
+        None => return Err(argument_type(runtime, "num", "a number or string", value)),
+    };
+
+    // Only plain decimal numbers, Rust would also take "inf" and "NaN".
+    let trimmed = string.trim();
+    let decimal = trimmed
+        .strip_prefix('-')
+        .unwrap_or(trimmed)
+        .chars()
+        .all(|c| c.is_ascii_digit() || c == '.');
+    match trimmed.parse::<f64>() {
+        Ok(number) if decimal => Ok(runtime.literal(LiteralValue::Number(number))),
+        _ => Err(NativeError::Conversion {
+            function: "num",
+            value: format!("{:?}", string),
+        }),
+    }
+}
+
+fn str_of<R: Runtime>(runtime: &mut R, arguments: &[R::Value]) -> Result<R::Value, NativeError> {
+    let string = runtime.stringify(&arguments[0]);
+    Ok(runtime.literal(LiteralValue::String(string.into())))
+}
+
+fn type_of<R: Runtime>(runtime: &mut R, arguments: &[R::Value]) -> Result<R::Value, NativeError> {
+    let name = runtime.value_type(&arguments[0]).to_string();
+    Ok(runtime.literal(LiteralValue::String(name.into())))
+}
+
+pub fn number<R: Runtime>(runtime: &R, function: &'static str, value: &R::Value) -> Result<f64, NativeError> {
+    runtime
+        .number(value)
+        .ok_or_else(|| argument_type(runtime, function, "a number", value))
+}
+
+pub fn argument_type<R: Runtime>(runtime: &R, function: &'static str, expected: &'static str, value: &R::Value) -> NativeError {
+    NativeError::ArgumentType {
+        function,
+        expected,
+        found: runtime.value_type(value),
+    }
+}
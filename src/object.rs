@@ -0,0 +1,161 @@
+use std::mem::size_of;
+use crate::class::{InstanceRef, LoxClass, LoxInstance};
+use crate::environment::{EnvRef, Environment};
+use crate::gc::{self, Allocation, Trace, Tracer};
+use crate::list::ListRef;
+use crate::map::{LoxMap, MapKey, MapRef};
+use crate::symbol::Symbol;
+use crate::token_type::LiteralValue;
+
+// What the tree-walker keeps on its garbage-collected heap: the kinds of
+// data that can end up in reference cycles.
+pub enum Object {
+    Environment(Environment),
+    Instance(LoxInstance),
+    List(Vec<LiteralValue>),
+    Map(LoxMap<LiteralValue>),
+}
+
+pub type Heap = gc::Heap<Object>;
+
+impl Heap {
+    pub fn environment(&self, environment: EnvRef) -> &Environment {
+        match self.get(environment.slot()) {
+            Object::Environment(environment) => environment,
+            _ => unreachable!("environment handle points at another object"),
+        }
+    }
+
+    pub fn environment_mut(&mut self, environment: EnvRef) -> &mut Environment {
+        match self.get_mut(environment.slot()) {
+            Object::Environment(environment) => environment,
+            _ => unreachable!("environment handle points at another object"),
+        }
+    }
+
+    pub fn instance(&self, instance: &InstanceRef) -> &LoxInstance {
+        match self.get(instance.slot()) {
+            Object::Instance(instance) => instance,
+            _ => unreachable!("instance handle points at another object"),
+        }
+    }
+
+    pub fn instance_mut(&mut self, instance: &InstanceRef) -> &mut LoxInstance {
+        match self.get_mut(instance.slot()) {
+            Object::Instance(instance) => instance,
+            _ => unreachable!("instance handle points at another object"),
+        }
+    }
+
+    pub fn list(&self, list: ListRef) -> &Vec<LiteralValue> {
+        match self.get(list.slot()) {
+            Object::List(elements) => elements,
+            _ => unreachable!("list handle points at another object"),
+        }
+    }
+
+    pub fn list_mut(&mut self, list: ListRef) -> &mut Vec<LiteralValue> {
+        match self.get_mut(list.slot()) {
+            Object::List(elements) => elements,
+            _ => unreachable!("list handle points at another object"),
+        }
+    }
+
+    pub fn map(&self, map: MapRef) -> &LoxMap<LiteralValue> {
+        match self.get(map.slot()) {
+            Object::Map(entries) => entries,
+            _ => unreachable!("map handle points at another object"),
+        }
+    }
+
+    pub fn map_mut(&mut self, map: MapRef) -> &mut LoxMap<LiteralValue> {
+        match self.get_mut(map.slot()) {
+            Object::Map(entries) => entries,
+            _ => unreachable!("map handle points at another object"),
+        }
+    }
+}
+
+impl Trace for Object {
+    fn trace(&self, tracer: &mut Tracer) {
+        match self {
+            Object::Environment(environment) => {
+                if let Some(enclosing) = environment.enclosing() {
+                    enclosing.trace(tracer);
+                }
+                for (_, value) in environment.variables() {
+                    value.trace(tracer);
+                }
+            }
+            Object::Instance(instance) => {
+                for (_, value) in instance.fields() {
+                    value.trace(tracer);
+                }
+            }
+            Object::List(elements) => {
+                for element in elements {
+                    element.trace(tracer);
+                }
+            }
+            Object::Map(entries) => {
+                for value in entries.values() {
+                    value.trace(tracer);
+                }
+            }
+        }
+    }
+}
+
+impl Allocation for Object {
+    fn size(&self) -> usize {
+        let entry = size_of::<Symbol>() + size_of::<LiteralValue>();
+        let variables: Box<dyn Iterator<Item = (&Symbol, &LiteralValue)>> = match self {
+            Object::Environment(environment) => Box::new(environment.variables()),
+            Object::Instance(instance) => Box::new(instance.fields()),
+            Object::List(elements) => return size_of::<Object>() + elements.len() * size_of::<LiteralValue>(),
+            Object::Map(entries) => {
+                let entry = size_of::<MapKey>() + size_of::<LiteralValue>();
+                return size_of::<Object>() + entries.len() * entry;
+            }
+        };
+
+        size_of::<Object>() + variables.map(|(name, _)| entry + name.len()).sum::<usize>()
+    }
+}
+
+impl Trace for EnvRef {
+    fn trace(&self, tracer: &mut Tracer) {
+        tracer.mark(self.slot());
+    }
+}
+
+// Functions and classes are reference counted themselves, but the scopes
+// they close over are on the heap.
+impl Trace for LiteralValue {
+    fn trace(&self, tracer: &mut Tracer) {
+        match self {
+            LiteralValue::Function(function) => function.closure().trace(tracer),
+            LiteralValue::Class(class) => trace_class(class, tracer),
+            LiteralValue::Instance(instance) => {
+                tracer.mark(instance.slot());
+                trace_class(instance.class(), tracer);
+            }
+            LiteralValue::List(list) => tracer.mark(list.slot()),
+            LiteralValue::Map(map) => tracer.mark(map.slot()),
+            _ => {}
+        }
+    }
+}
+
+fn trace_class(class: &LoxClass, tracer: &mut Tracer) {
+    if !tracer.first_visit(class) {
+        return;
+    }
+
+    for method in class.methods() {
+        method.closure().trace(tracer);
+    }
+    if let Some(superclass) = class.superclass() {
+        trace_class(superclass, tracer);
+    }
+}
@@ -1,40 +1,129 @@
-use crate::expr::{Assign, Binary, Expr, Grouping, Literal, Unary, Variable};
-use crate::stmt::{Block, Expression, Print, Stmt, Var};
-use crate::token::Token;
+use std::collections::VecDeque;
+use std::rc::Rc;
+use crate::expr::{Assign, Binary, Call, Expr, ExprId, Get, GetIndex, Grouping, Interpolation, List, Literal, Logical, Map, Set, SetIndex, Super, This, Unary, Variable};
+use crate::stmt::{Block, Class, Expression, Function, If, Print, Return, Stmt, Var, While};
+use crate::error::{ParseError, ScanError};
+use crate::token::{Position, Span, Token};
+use crate::symbol::Symbol;
 use crate::token_type::{LiteralValue, TokenType};
 
-pub struct Parser {
-    tokens: Vec<Token>,
-    current: usize,
+// Pulls tokens from the stream as it needs them, so parsing starts before the
+// whole input has been scanned. Scan errors are set aside for the caller,
+// which reports them ahead of any parse errors they may have caused.
+pub struct Parser<I> {
+    tokens: I,
+    lookahead: VecDeque<Token>,
+    previous: Option<Token>,
+    errors: Vec<ParseError>,
+    scan_errors: Vec<ScanError>,
 }
 
-impl Parser {
-    pub fn new(tokens: Vec<Token>) -> Parser {
-        Parser {
+impl<I: Iterator<Item = Result<Token, ScanError>>> Parser<I> {
+    pub fn new(tokens: I) -> Parser<I> {
+        let mut parser = Parser {
             tokens,
-            current: 0,
-        }
+            lookahead: VecDeque::new(),
+            previous: None,
+            errors: Vec::new(),
+            scan_errors: Vec::new(),
+        };
+        parser.fill(1);
+        parser
+    }
+
+    pub fn scan_errors(&self) -> &[ScanError] {
+        &self.scan_errors
     }
 
-    pub fn parse(&mut self) -> Result<Vec<Stmt>, String> {
+    // Keeps going after a syntax error so every error in the file is reported
+    // at once, the statements that did parse are returned alongside them.
+    pub fn parse(&mut self) -> (Vec<Stmt>, Vec<ParseError>) {
         let mut stmts: Vec<Stmt> = Vec::new();
 
         while !self.is_at_end() {
-            stmts.push(self.declaration()?);
+            if let Some(stmt) = self.declaration() {
+                stmts.push(stmt);
+            }
         }
 
-        Ok(stmts)
+        (stmts, std::mem::take(&mut self.errors))
+    }
+
+    fn declaration(&mut self) -> Option<Stmt> {
+        let result = if self.compare(&[TokenType::Class]) {
+            self.class_declaration()
+        } else if self.compare(&[TokenType::Fun]) {
+            self.function("function").map(Stmt::Function)
+        } else if self.compare(&[TokenType::Var]) {
+            self.var_declaration()
+        } else {
+            self.statement()
+        };
+
+        self.recover(result)
+    }
+
+    fn recover(&mut self, result: Result<Stmt, ParseError>) -> Option<Stmt> {
+        match result {
+            Ok(stmt) => Some(stmt),
+            Err(err) => {
+                self.errors.push(err);
+                self.synchronize();
+                None
+            }
+        }
     }
 
-    fn declaration(&mut self) -> Result<Stmt, String> {
-        if self.compare(&[TokenType::Var]) {
-            return Ok(self.var_declaration()?);
+    fn class_declaration(&mut self) -> Result<Stmt, ParseError> {
+        let name = self.consume(TokenType::Identifier, "Expect class name.")?.clone();
+
+        let superclass = if self.compare(&[TokenType::Less]) {
+            let name = self.consume(TokenType::Identifier, "Expect superclass name.")?.clone();
+            Some(Variable { id: ExprId::fresh(), name })
+        } else {
+            None
+        };
+
+        self.consume(TokenType::LeftBrace, "Expect '{' before class body.")?;
+
+        let mut methods = Vec::new();
+        while !self.check(&TokenType::RightBrace) && !self.is_at_end() {
+            methods.push(self.function("method")?);
         }
 
-        Ok(self.statement()?)
+        self.consume(TokenType::RightBrace, "Expect '}' after class body.")?;
+
+        Ok(Stmt::Class(Class { name, superclass, methods }))
     }
 
-    fn var_declaration(&mut self) -> Result<Stmt, String> {
+    fn function(&mut self, kind: &str) -> Result<Rc<Function>, ParseError> {
+        let name = self.consume(TokenType::Identifier, &format!("Expect {} name.", kind))?.clone();
+
+        self.consume(TokenType::LeftParan, &format!("Expect '(' after {} name.", kind))?;
+        let mut params = Vec::new();
+        if !self.check(&TokenType::RightParan) {
+            loop {
+                if params.len() >= 255 {
+                    let err = self.error(self.peek(), "Can't have more than 255 parameters.");
+                    self.errors.push(err);
+                }
+
+                params.push(self.consume(TokenType::Identifier, "Expect parameter name.")?.clone());
+
+                if !self.compare(&[TokenType::Comma]) {
+                    break;
+                }
+            }
+        }
+        self.consume(TokenType::RightParan, "Expect ')' after parameters.")?;
+
+        self.consume(TokenType::LeftBrace, &format!("Expect '{{' before {} body.", kind))?;
+        let body = self.block()?;
+
+        Ok(Rc::new(Function { name, params, body }))
+    }
+
+    fn var_declaration(&mut self) -> Result<Stmt, ParseError> {
         let name = self.consume(TokenType::Identifier, "Expect variable name")?.clone();
 
         let mut initializer = Expr::Literal(Literal { value: LiteralValue::Nil });
@@ -46,72 +135,323 @@ impl Parser {
         Ok(Stmt::Var(Var { name, initializer: Option::from(Box::new(initializer)) }))
     }
 
-    fn statement(&mut self) -> Result<Stmt, String> {
+    fn statement(&mut self) -> Result<Stmt, ParseError> {
+        if self.compare(&[TokenType::For]) {
+            return self.for_statement()
+        }
+
+        if self.compare(&[TokenType::If]) {
+            return self.if_statement()
+        }
+
         if self.compare(&[TokenType::Print]) {
             return self.print_statement()
         }
 
+        if self.compare(&[TokenType::Return]) {
+            return self.return_statement()
+        }
+
+        if self.compare(&[TokenType::While]) {
+            return self.while_statement()
+        }
+
         if self.compare(&[TokenType::LeftBrace]) {
-            return self.block_statement()
+            return self.block_or_map_statement()
         }
 
         self.expression_statement()
     }
 
-    fn block_statement(&mut self) -> Result<Stmt, String> {
+    // A `{` opens a block unless its first expression is followed by a `:`,
+    // then it was a map literal starting an expression statement.
+    fn block_or_map_statement(&mut self) -> Result<Stmt, ParseError> {
+        let brace = self.previous().clone();
+        let mut statements = Vec::new();
+
+        if self.starts_expression() {
+            match self.expression() {
+                Ok(key) if self.compare(&[TokenType::Colon]) => {
+                    let value = self.expression()?;
+                    let map = self.map(brace, (key, value))?;
+                    let expr = self.continue_expression(map)?;
+                    return self.finish_expression_statement(expr)
+                }
+                result => {
+                    let stmt = result.and_then(|expr| self.finish_expression_statement(expr));
+                    statements.extend(self.recover(stmt));
+                }
+            }
+        }
+
+        statements.extend(self.block()?);
+        Ok(Stmt::Block(Block { statements }))
+    }
+
+    // Declarations and statements start with a keyword, a nested block with
+    // a `{`.
+    fn starts_expression(&self) -> bool {
+        matches!(
+            self.peek().token_type,
+            TokenType::LeftParan
+                | TokenType::LeftBracket
+                | TokenType::Minus
+                | TokenType::Bang
+                | TokenType::String
+                | TokenType::Interpolation
+                | TokenType::Number
+                | TokenType::Identifier
+                | TokenType::False
+                | TokenType::Nil
+                | TokenType::Super
+                | TokenType::This
+                | TokenType::True
+        )
+    }
+
+    fn block(&mut self) -> Result<Vec<Stmt>, ParseError> {
         let mut statements = Vec::new();
 
         while !self.is_at_end() && !self.check(&TokenType::RightBrace) {
-            statements.push(Box::from(self.declaration()?));
+            if let Some(stmt) = self.declaration() {
+                statements.push(stmt);
+            }
         }
 
         self.consume(TokenType::RightBrace, "Expect '}' after block")?;
-        Ok(Stmt::Block(Block { statements }))
+        Ok(statements)
     }
 
-    fn expression_statement(&mut self) -> Result<Stmt, String> {
+    fn expression_statement(&mut self) -> Result<Stmt, ParseError> {
         let expr = self.expression()?;
-        self.consume(TokenType::Semicolon, "Expect ';'");
+        self.finish_expression_statement(expr)
+    }
+
+    fn finish_expression_statement(&mut self, expr: Expr) -> Result<Stmt, ParseError> {
+        self.consume(TokenType::Semicolon, "Expect ';'")?;
         Ok(Stmt::Expression(Expression { expression: Box::new(expr) }))
     }
 
-    fn print_statement(&mut self) -> Result<Stmt, String> {
+    // A `for` loop has no node of its own, it is desugared into a block
+    // holding the initializer and a `while` loop whose body runs the increment.
+    fn for_statement(&mut self) -> Result<Stmt, ParseError> {
+        self.consume(TokenType::LeftParan, "Expect '(' after 'for'.")?;
+
+        let initializer = if self.compare(&[TokenType::Semicolon]) {
+            None
+        } else if self.compare(&[TokenType::Var]) {
+            Some(self.var_declaration()?)
+        } else {
+            Some(self.expression_statement()?)
+        };
+
+        let condition = if self.check(&TokenType::Semicolon) {
+            Expr::Literal(Literal { value: LiteralValue::Bool(true) })
+        } else {
+            self.expression()?
+        };
+        self.consume(TokenType::Semicolon, "Expect ';' after loop condition.")?;
+
+        let increment = if self.check(&TokenType::RightParan) {
+            None
+        } else {
+            Some(self.expression()?)
+        };
+        self.consume(TokenType::RightParan, "Expect ')' after for clauses.")?;
+
+        let mut body = self.statement()?;
+
+        if let Some(increment) = increment {
+            body = Stmt::Block(Block {
+                statements: vec![
+                    body,
+                    Stmt::Expression(Expression { expression: Box::new(increment) }),
+                ],
+            });
+        }
+
+        body = Stmt::While(While {
+            condition: Box::new(condition),
+            body: Box::new(body),
+        });
+
+        if let Some(initializer) = initializer {
+            body = Stmt::Block(Block { statements: vec![initializer, body] });
+        }
+
+        Ok(body)
+    }
+
+    fn if_statement(&mut self) -> Result<Stmt, ParseError> {
+        self.consume(TokenType::LeftParan, "Expect '(' after 'if'.")?;
+        let condition = self.expression()?;
+        self.consume(TokenType::RightParan, "Expect ')' after if condition.")?;
+
+        let then_branch = Box::new(self.statement()?);
+        let else_branch = if self.compare(&[TokenType::Else]) {
+            Some(Box::new(self.statement()?))
+        } else {
+            None
+        };
+
+        Ok(Stmt::If(If {
+            condition: Box::new(condition),
+            then_branch,
+            else_branch,
+        }))
+    }
+
+    fn while_statement(&mut self) -> Result<Stmt, ParseError> {
+        self.consume(TokenType::LeftParan, "Expect '(' after 'while'.")?;
+        let condition = self.expression()?;
+        self.consume(TokenType::RightParan, "Expect ')' after condition.")?;
+        let body = self.statement()?;
+
+        Ok(Stmt::While(While {
+            condition: Box::new(condition),
+            body: Box::new(body),
+        }))
+    }
+
+    fn print_statement(&mut self) -> Result<Stmt, ParseError> {
         let value = self.expression()?;
-        self.consume(TokenType::Semicolon, "Expect ';'");
+        self.consume(TokenType::Semicolon, "Expect ';'")?;
 
         Ok(Stmt::Print(Print {
             expression: Box::new(value),
         }))
     }
 
-    pub fn expression(&mut self) -> Result<Expr, String> {
+    fn return_statement(&mut self) -> Result<Stmt, ParseError> {
+        let keyword = self.previous().clone();
+
+        let value = if self.check(&TokenType::Semicolon) {
+            None
+        } else {
+            Some(Box::new(self.expression()?))
+        };
+
+        self.consume(TokenType::Semicolon, "Expect ';' after return value.")?;
+        Ok(Stmt::Return(Return { keyword, value }))
+    }
+
+    // Parses a lone expression, as used by the `parse` and `evaluate` commands.
+    pub fn parse_expression(&mut self) -> Result<Expr, Vec<ParseError>> {
+        match self.expression() {
+            Ok(expr) if self.errors.is_empty() => Ok(expr),
+            Ok(_) => Err(std::mem::take(&mut self.errors)),
+            Err(err) => {
+                self.errors.push(err);
+                Err(std::mem::take(&mut self.errors))
+            }
+        }
+    }
+
+    fn expression(&mut self) -> Result<Expr, ParseError> {
         self.assignment()
     }
 
-    fn assignment(&mut self) -> Result<Expr, String> {
-        let expr = self.equality()?;
+    // Parses the rest of an expression whose leftmost operand is `operand`,
+    // going through every level of precedence from the tightest.
+    fn continue_expression(&mut self, operand: Expr) -> Result<Expr, ParseError> {
+        let expr = self.continue_call(operand)?;
+        let expr = self.continue_factor(expr)?;
+        let expr = self.continue_term(expr)?;
+        let expr = self.continue_comparison(expr)?;
+        let expr = self.continue_equality(expr)?;
+        let expr = self.continue_and(expr)?;
+        let expr = self.continue_or(expr)?;
+        self.continue_assignment(expr)
+    }
 
+    fn assignment(&mut self) -> Result<Expr, ParseError> {
+        let expr = self.or()?;
+        self.continue_assignment(expr)
+    }
+
+    fn continue_assignment(&mut self, expr: Expr) -> Result<Expr, ParseError> {
         if self.compare(&[TokenType::Equal]) {
             let equals = self.previous().clone();
             let value = self.assignment();
 
-            if let Expr::Variable(var) = expr {
-                let name = var.name;
-                return Ok(Expr::Assign(Assign {
-                    name,
+            return match expr {
+                Expr::Variable(var) => Ok(Expr::Assign(Assign {
+                    id: ExprId::fresh(),
+                    name: var.name,
                     value: Box::new(value?),
-                }));
-            }
+                })),
+                Expr::Get(get) => Ok(Expr::Set(Set {
+                    object: get.object,
+                    name: get.name,
+                    value: Box::new(value?),
+                })),
+                Expr::GetIndex(get) => Ok(Expr::SetIndex(SetIndex {
+                    object: get.object,
+                    bracket: get.bracket,
+                    index: get.index,
+                    value: Box::new(value?),
+                })),
+                expr => {
+                    // The parser is not in a confused state, so there is no need to synchronize.
+                    let err = self.error(&equals, "Invalid assignment target.");
+                    self.errors.push(err);
+                    value?;
+                    Ok(expr)
+                }
+            };
+        }
+
+        Ok(expr)
+    }
+
+    fn or(&mut self) -> Result<Expr, ParseError> {
+        let expr = self.and()?;
+        self.continue_or(expr)
+    }
 
-            self.error(&equals, "Expect '=' after assignment");
-            return Err("Expect = after assignment".to_string());
+    fn continue_or(&mut self, mut expr: Expr) -> Result<Expr, ParseError> {
+
+        while self.compare(&[TokenType::Or]) {
+            let operator = self.previous().clone();
+            let right = self.and()?;
+
+            expr = Expr::Logical(Logical {
+                left: Box::new(expr),
+                operator,
+                right: Box::new(right),
+            })
+        }
+
+        Ok(expr)
+    }
+
+    fn and(&mut self) -> Result<Expr, ParseError> {
+        let expr = self.equality()?;
+        self.continue_and(expr)
+    }
+
+    fn continue_and(&mut self, mut expr: Expr) -> Result<Expr, ParseError> {
+
+        while self.compare(&[TokenType::And]) {
+            let operator = self.previous().clone();
+            let right = self.equality()?;
+
+            expr = Expr::Logical(Logical {
+                left: Box::new(expr),
+                operator,
+                right: Box::new(right),
+            })
         }
 
         Ok(expr)
     }
 
-    fn equality(&mut self) -> Result<Expr, String> {
-        let mut expr = self.comparison()?;
+    fn equality(&mut self) -> Result<Expr, ParseError> {
+        let expr = self.comparison()?;
+        self.continue_equality(expr)
+    }
+
+    fn continue_equality(&mut self, mut expr: Expr) -> Result<Expr, ParseError> {
 
         while self.compare(&[TokenType::EqualEqual, TokenType::BangEqual]) {
             let operator = self.previous().clone();
@@ -127,8 +467,12 @@ impl Parser {
         Ok(expr)
     }
 
-    fn comparison(&mut self) -> Result<Expr, String> {
-        let mut expr = self.term()?;
+    fn comparison(&mut self) -> Result<Expr, ParseError> {
+        let expr = self.term()?;
+        self.continue_comparison(expr)
+    }
+
+    fn continue_comparison(&mut self, mut expr: Expr) -> Result<Expr, ParseError> {
 
         while self.compare(&[
             TokenType::Greater,
@@ -149,8 +493,12 @@ impl Parser {
         Ok(expr)
     }
 
-    fn term(&mut self) -> Result<Expr, String> {
-        let mut expr = self.factor()?;
+    fn term(&mut self) -> Result<Expr, ParseError> {
+        let expr = self.factor()?;
+        self.continue_term(expr)
+    }
+
+    fn continue_term(&mut self, mut expr: Expr) -> Result<Expr, ParseError> {
 
         while self.compare(&[TokenType::Minus, TokenType::Plus]) {
             let operator = self.previous().clone();
@@ -166,8 +514,12 @@ impl Parser {
         Ok(expr)
     }
 
-    fn factor(&mut self) -> Result<Expr, String> {
-        let mut expr = self.unary()?;
+    fn factor(&mut self) -> Result<Expr, ParseError> {
+        let expr = self.unary()?;
+        self.continue_factor(expr)
+    }
+
+    fn continue_factor(&mut self, mut expr: Expr) -> Result<Expr, ParseError> {
 
         while self.compare(&[TokenType::Slash, TokenType::Star]) {
             let operator = self.previous().clone();
@@ -183,7 +535,7 @@ impl Parser {
         Ok(expr)
     }
 
-    fn unary(&mut self) -> Result<Expr, String> {
+    fn unary(&mut self) -> Result<Expr, ParseError> {
         if self.compare(&[TokenType::Bang, TokenType::Minus]) {
             let operator = self.previous().clone();
             let right = self.unary()?;
@@ -193,10 +545,71 @@ impl Parser {
             }));
         }
 
-        self.primary()
+        self.call()
     }
 
-    fn primary(&mut self) -> Result<Expr, String> {
+    fn call(&mut self) -> Result<Expr, ParseError> {
+        let expr = self.primary()?;
+        self.continue_call(expr)
+    }
+
+    // Calls, property accesses and indexing applied to `expr`.
+    fn continue_call(&mut self, mut expr: Expr) -> Result<Expr, ParseError> {
+
+        loop {
+            if self.compare(&[TokenType::LeftParan]) {
+                expr = self.finish_call(expr)?;
+            } else if self.compare(&[TokenType::Dot]) {
+                let name = self.consume(TokenType::Identifier, "Expect property name after '.'.")?.clone();
+                expr = Expr::Get(Get {
+                    object: Box::new(expr),
+                    name,
+                });
+            } else if self.compare(&[TokenType::LeftBracket]) {
+                let bracket = self.previous().clone();
+                let index = self.expression()?;
+                self.consume(TokenType::RightBracket, "Expect ']' after index.")?;
+                expr = Expr::GetIndex(GetIndex {
+                    object: Box::new(expr),
+                    bracket,
+                    index: Box::new(index),
+                });
+            } else {
+                break;
+            }
+        }
+
+        Ok(expr)
+    }
+
+    fn finish_call(&mut self, callee: Expr) -> Result<Expr, ParseError> {
+        let mut arguments = Vec::new();
+
+        if !self.check(&TokenType::RightParan) {
+            loop {
+                if arguments.len() >= 255 {
+                    let err = self.error(self.peek(), "Can't have more than 255 arguments.");
+                    self.errors.push(err);
+                }
+
+                arguments.push(self.expression()?);
+
+                if !self.compare(&[TokenType::Comma]) {
+                    break;
+                }
+            }
+        }
+
+        let paren = self.consume(TokenType::RightParan, "Expect ')' after arguments.")?.clone();
+
+        Ok(Expr::Call(Call {
+            callee: Box::new(callee),
+            paren,
+            arguments,
+        }))
+    }
+
+    fn primary(&mut self) -> Result<Expr, ParseError> {
         if self.compare(&[TokenType::False]) {
             return Ok(Expr::Literal(Literal {
                 value: LiteralValue::Bool(false),
@@ -219,6 +632,50 @@ impl Parser {
             }))
         }
 
+        if self.compare(&[TokenType::Interpolation]) {
+            return self.interpolation();
+        }
+
+        if self.compare(&[TokenType::LeftBracket]) {
+            let bracket = self.previous().clone();
+            let mut elements = Vec::new();
+            if !self.check(&TokenType::RightBracket) {
+                loop {
+                    elements.push(self.expression()?);
+
+                    if !self.compare(&[TokenType::Comma]) {
+                        break;
+                    }
+                }
+            }
+
+            self.consume(TokenType::RightBracket, "Expect ']' after list elements.")?;
+            return Ok(Expr::List(List { bracket, elements }));
+        }
+
+        if self.compare(&[TokenType::LeftBrace]) {
+            let brace = self.previous().clone();
+            if self.compare(&[TokenType::RightBrace]) {
+                return Ok(Expr::Map(Map { brace, entries: Vec::new() }));
+            }
+
+            let key = self.expression()?;
+            self.consume(TokenType::Colon, "Expect ':' after map key.")?;
+            let value = self.expression()?;
+            return self.map(brace, (key, value));
+        }
+
+        if self.compare(&[TokenType::Super]) {
+            let keyword = self.previous().clone();
+            self.consume(TokenType::Dot, "Expect '.' after 'super'.")?;
+            let method = self.consume(TokenType::Identifier, "Expect superclass method name.")?.clone();
+            return Ok(Expr::Super(Super { id: ExprId::fresh(), keyword, method }))
+        }
+
+        if self.compare(&[TokenType::This]) {
+            return Ok(Expr::This(This { id: ExprId::fresh(), keyword: self.previous().clone() }))
+        }
+
         if self.compare(&[TokenType::LeftParan]) {
             let expr = self.expression()?;
             self.consume(TokenType::RightParan, "Expect ')' after expression.")?;
@@ -228,13 +685,54 @@ impl Parser {
         }
 
         if self.compare(&[TokenType::Identifier]) {
-            return Ok(Expr::Variable(Variable { name: self.previous().clone() }))
+            return Ok(Expr::Variable(Variable { id: ExprId::fresh(), name: self.previous().clone() }))
         }
 
-        Err(self.error(self.peek(), "Expect expression.").to_string())
+        Err(self.error(self.peek(), "Expect expression."))
+    }
+
+    // The rest of a map literal after its first entry.
+    fn map(&mut self, brace: Token, first: (Expr, Expr)) -> Result<Expr, ParseError> {
+        let mut entries = vec![first];
+        while self.compare(&[TokenType::Comma]) {
+            let key = self.expression()?;
+            self.consume(TokenType::Colon, "Expect ':' after map key.")?;
+            entries.push((key, self.expression()?));
+        }
+
+        self.consume(TokenType::RightBrace, "Expect '}' after map entries.")?;
+        Ok(Expr::Map(Map { brace, entries }))
+    }
+
+    // Called with the first `Interpolation` token consumed. It and every
+    // `InterpolationMiddle` are followed by an expression, an
+    // `InterpolationEnd` ends the literal.
+    fn interpolation(&mut self) -> Result<Expr, ParseError> {
+        let start = self.previous().clone();
+        let mut parts = Vec::new();
+
+        loop {
+            if let Some(LiteralValue::String(segment)) = &self.previous().literal {
+                if !segment.is_empty() {
+                    parts.push(Expr::Literal(Literal {
+                        value: LiteralValue::String(segment.clone()),
+                    }));
+                }
+            }
+
+            if self.previous().token_type == TokenType::InterpolationEnd {
+                return Ok(Expr::Interpolation(Interpolation { start, parts }));
+            }
+
+            parts.push(self.expression()?);
+
+            if !self.compare(&[TokenType::InterpolationMiddle]) {
+                self.consume(TokenType::InterpolationEnd, "Expect '}' after interpolated expression.")?;
+            }
+        }
     }
 
-    fn consume(&mut self, token_type: TokenType, message: &str) -> Result<&Token, String> {
+    fn consume(&mut self, token_type: TokenType, message: &str) -> Result<&Token, ParseError> {
         if self.check(&token_type) {
             return Ok(self.advance());
         }
@@ -242,19 +740,59 @@ impl Parser {
         Err(self.error(self.peek(), message))
     }
 
-    fn error(&self, token: &Token, message: &str) -> String {
-        if token.token_type == TokenType::EOF {
-            format!("[line {}] Error at end: {}", token.line, message)
-        } else {
-            format!(
-                "[line {}] Error at '{}': {}",
-                token.line, token.lexeme, message
-            )
+    fn error(&self, token: &Token, message: &str) -> ParseError {
+        ParseError::new(token.clone(), message)
+    }
+
+    // Discards tokens until the start of what is probably the next statement.
+    fn synchronize(&mut self) {
+        self.advance();
+
+        while !self.is_at_end() {
+            if self.previous().token_type == TokenType::Semicolon {
+                return;
+            }
+
+            match self.peek().token_type {
+                TokenType::Class
+                | TokenType::Fun
+                | TokenType::Var
+                | TokenType::For
+                | TokenType::If
+                | TokenType::While
+                | TokenType::Print
+                | TokenType::Return => return,
+                _ => {}
+            }
+
+            self.advance();
+        }
+    }
+
+    // Makes sure at least `count` tokens are buffered. A stream that ends
+    // without an `Eof` gets one, and the `Eof` is repeated from then on.
+    fn fill(&mut self, count: usize) {
+        while self.lookahead.len() < count {
+            let token = match self.lookahead.back() {
+                Some(last) if last.token_type == TokenType::Eof => last.clone(),
+                _ => match self.tokens.next() {
+                    Some(Ok(token)) => token,
+                    Some(Err(err)) => {
+                        self.scan_errors.push(err);
+                        continue;
+                    }
+                    None => {
+                        let position = self.lookahead.back().or(self.previous.as_ref()).map_or(Position::default(), |token| token.end);
+                        Token::new(TokenType::Eof, Symbol::intern(""), None, position, position, Span::default())
+                    }
+                },
+            };
+            self.lookahead.push_back(token);
         }
     }
 
     fn peek(&self) -> &Token {
-        &self.tokens[self.current]
+        &self.lookahead[0]
     }
 
     fn compare(&mut self, types: &[TokenType]) -> bool {
@@ -276,21 +814,18 @@ impl Parser {
         }
     }
 
-    fn is_at_end(&self) -> bool {
-        if self.peek().token_type == TokenType::EOF {
-            true
-        } else {
-            false
-        }
+    pub fn is_at_end(&self) -> bool {
+        self.peek().token_type == TokenType::Eof
     }
 
     fn previous(&self) -> &Token {
-        &self.tokens[self.current - 1]
+        self.previous.as_ref().expect("no token has been consumed yet")
     }
 
     fn advance(&mut self) -> &Token {
         if !self.is_at_end() {
-            self.current += 1
+            self.previous = self.lookahead.pop_front();
+            self.fill(1);
         }
         self.previous()
     }
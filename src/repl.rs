@@ -0,0 +1,223 @@
+use crate::error::{LoxError, ScanErrorKind};
+use crate::gc::{GcConfig, GcStats};
+use crate::interpreter::Interpreter;
+use crate::parser::Parser;
+use crate::resolver::Resolver;
+use crate::scanner::Scanner;
+use crate::symbol::Symbol;
+use crate::token::Token;
+use crate::token_type::TokenType;
+use std::collections::HashSet;
+use std::io::{self, BufRead, Write};
+
+const HELP: &str = "\
+Enter Lox statements or bare expressions, unfinished input continues on the next line.
+  :help        show this message
+  :env         list the global variables
+  :history     list the inputs entered so far
+  :history <n> run input <n> again
+  :cancel      discard the unfinished input
+  :quit        leave the REPL (Ctrl-D works too)";
+
+// What to do with the text typed so far.
+enum Input {
+    Complete,
+    Incomplete,
+}
+
+pub struct Repl {
+    interpreter: Interpreter,
+    history: Vec<String>,
+    buffer: String,
+    // The globals every session starts with, natives and constants alike.
+    builtins: HashSet<Symbol>,
+}
+
+impl Default for Repl {
+    fn default() -> Self {
+        Repl::new()
+    }
+}
+
+impl Repl {
+    pub fn new() -> Repl {
+        Repl::with_gc(GcConfig::default())
+    }
+
+    pub fn with_gc(config: GcConfig) -> Repl {
+        let interpreter = Interpreter::with_gc(config);
+        let builtins = interpreter.globals().variables().map(|(name, _)| name.clone()).collect();
+
+        Repl {
+            interpreter,
+            history: Vec::new(),
+            buffer: String::new(),
+            builtins,
+        }
+    }
+
+    pub fn gc_stats(&self) -> GcStats {
+        self.interpreter.gc_stats()
+    }
+
+    pub fn run(&mut self) -> io::Result<()> {
+        let stdin = io::stdin();
+        let mut lines = stdin.lock().lines();
+
+        loop {
+            print!("{}", if self.buffer.is_empty() { "> " } else { "... " });
+            io::stdout().flush()?;
+
+            let line = match lines.next() {
+                Some(line) => line?,
+                None => {
+                    println!();
+                    return Ok(());
+                }
+            };
+
+            // Commands work in the middle of unfinished input too, so `:cancel`
+            // can get out of it.
+            if line.trim_start().starts_with(':') {
+                if !self.meta_command(line.trim()) {
+                    return Ok(());
+                }
+                continue;
+            }
+
+            self.submit(&line);
+        }
+    }
+
+    // Adds a line to the unfinished input and runs it once it is complete.
+    fn submit(&mut self, line: &str) {
+        self.buffer.push_str(line);
+        self.buffer.push('\n');
+
+        if let Input::Complete = self.eval(self.buffer.clone()) {
+            let entry = std::mem::take(&mut self.buffer);
+            self.history.push(entry.trim_end().to_string());
+        }
+    }
+
+    // Returns false once the user asked to leave.
+    fn meta_command(&mut self, command: &str) -> bool {
+        let mut words = command.split_whitespace();
+        match (words.next().unwrap_or_default(), words.next()) {
+            (":quit" | ":q", None) => return false,
+            (":help" | ":h", None) => println!("{}", HELP),
+            (":cancel", None) => self.buffer.clear(),
+            (":env", None) => {
+                // The builtins would only be noise.
+                let mut variables: Vec<_> = self
+                    .interpreter
+                    .globals()
+                    .variables()
+                    .filter(|(name, _)| !self.builtins.contains(*name))
+                    .collect();
+                variables.sort_by_key(|(name, _)| *name);
+
+                for (name, value) in variables {
+                    println!("{} = {}", name, self.interpreter.stringify(value));
+                }
+            }
+            (":history", None) => {
+                for (i, entry) in self.history.iter().enumerate() {
+                    println!("{:>4}  {}", i + 1, entry.replace('\n', "\n      "));
+                }
+            }
+            // The entry is echoed and then runs as if it had been typed again.
+            (":history", Some(number)) => {
+                let entry = number
+                    .parse::<usize>()
+                    .ok()
+                    .and_then(|number| number.checked_sub(1))
+                    .and_then(|index| self.history.get(index))
+                    .cloned();
+
+                match entry {
+                    Some(entry) => {
+                        println!("{}", entry);
+                        self.submit(&entry);
+                    }
+                    None => println!("No input {} in the history", number),
+                }
+            }
+            _ => println!("Unknown command {}, try :help", command),
+        }
+
+        true
+    }
+
+    fn eval(&mut self, source: String) -> Input {
+        let (tokens, scan_errors) = Scanner::new(source).scan_tokens();
+
+        let unterminated = [ScanErrorKind::UnterminatedString, ScanErrorKind::UnterminatedComment];
+        if !scan_errors.is_empty() && scan_errors.iter().all(|err| unterminated.contains(&err.kind)) {
+            return Input::Incomplete;
+        }
+        if !scan_errors.is_empty() {
+            Repl::report(scan_errors.into_iter().map(LoxError::from));
+            return Input::Complete;
+        }
+
+        if Repl::is_unbalanced(&tokens) {
+            return Input::Incomplete;
+        }
+
+        let (statements, errors) = Parser::new(tokens.iter().cloned().map(Ok)).parse();
+        if errors.is_empty() {
+            let mut resolver = Resolver::new(&mut self.interpreter);
+            if let Err(errors) = resolver.resolve_program(&statements) {
+                Repl::report(errors.into_iter().map(LoxError::from));
+                return Input::Complete;
+            }
+
+            if let Err(err) = self.interpreter.interpret(&statements) {
+                Repl::report([LoxError::from(err)]);
+            }
+            return Input::Complete;
+        }
+
+        // A bare expression without the trailing `;` has its value echoed.
+        let mut parser = Parser::new(tokens.into_iter().map(Ok));
+        if let Ok(expression) = parser.parse_expression() {
+            if parser.is_at_end() {
+                match self.interpreter.interpret_expression(&expression) {
+                    Ok(value) => println!("{}", self.interpreter.stringify(&value)),
+                    Err(err) => Repl::report([LoxError::from(err)]),
+                }
+                return Input::Complete;
+            }
+        }
+
+        // Running out of tokens, e.g. a missing `;`, means the input goes on.
+        // Any other error is reported right away.
+        if errors.iter().all(|err| err.token.token_type == TokenType::Eof) {
+            return Input::Incomplete;
+        }
+
+        Repl::report(errors.into_iter().map(LoxError::from));
+        Input::Complete
+    }
+
+    fn is_unbalanced(tokens: &[Token]) -> bool {
+        let mut depth = 0;
+
+        for token in tokens {
+            match token.token_type {
+                TokenType::LeftParan | TokenType::LeftBrace | TokenType::LeftBracket => depth += 1,
+                TokenType::RightParan | TokenType::RightBrace | TokenType::RightBracket => depth -= 1,
+                _ => {}
+            }
+        }
+
+        depth > 0
+    }
+
+    fn report(errors: impl IntoIterator<Item = LoxError>) {
+        for err in errors {
+            eprintln!("{}", err);
+        }
+    }
+}
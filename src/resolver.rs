@@ -0,0 +1,336 @@
+use std::collections::HashMap;
+use std::rc::Rc;
+use crate::expr::{Assign, Binary, Call, Expr, ExprId, Get, GetIndex, Grouping, Interpolation, List, Literal, Logical, Map, Set, SetIndex, Super, This, Unary, Variable, Visitor as ExprVisitor};
+use crate::error::ResolveError;
+use crate::interpreter::Interpreter;
+use crate::stmt::{Block, Class, Expression, Function, If, Print, Return, Stmt, Var, While, Visitor as StmtVisitor};
+use crate::symbol::Symbol;
+use crate::token::Token;
+
+#[derive(Clone, Copy, PartialEq)]
+enum FunctionType {
+    None,
+    Function,
+    Initializer,
+    Method,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum ClassType {
+    None,
+    Class,
+    Subclass,
+}
+
+pub struct Resolver<'a> {
+    interpreter: &'a mut Interpreter,
+    // Each scope maps a name to whether its initializer has finished running.
+    scopes: Vec<HashMap<Symbol, bool>>,
+    current_function: FunctionType,
+    current_class: ClassType,
+    errors: Vec<ResolveError>,
+}
+
+impl ExprVisitor<()> for Resolver<'_> {
+    fn visit_assign_expr(&mut self, expr: &Assign) {
+        self.resolve_expr(&expr.value);
+        self.resolve_local(expr.id, &expr.name);
+    }
+
+    fn visit_binary_expr(&mut self, expr: &Binary) {
+        self.resolve_expr(&expr.left);
+        self.resolve_expr(&expr.right);
+    }
+
+    fn visit_call_expr(&mut self, expr: &Call) {
+        self.resolve_expr(&expr.callee);
+
+        for argument in &expr.arguments {
+            self.resolve_expr(argument);
+        }
+    }
+
+    fn visit_get_expr(&mut self, expr: &Get) {
+        self.resolve_expr(&expr.object);
+    }
+
+    fn visit_grouping_expr(&mut self, expr: &Grouping) {
+        self.resolve_expr(&expr.expression);
+    }
+
+    fn visit_get_index_expr(&mut self, expr: &GetIndex) {
+        self.resolve_expr(&expr.object);
+        self.resolve_expr(&expr.index);
+    }
+
+    fn visit_interpolation_expr(&mut self, expr: &Interpolation) {
+        for part in &expr.parts {
+            self.resolve_expr(part);
+        }
+    }
+
+    fn visit_list_expr(&mut self, expr: &List) {
+        for element in &expr.elements {
+            self.resolve_expr(element);
+        }
+    }
+
+    fn visit_literal_expr(&mut self, _expr: &Literal) {}
+
+    fn visit_logical_expr(&mut self, expr: &Logical) {
+        self.resolve_expr(&expr.left);
+        self.resolve_expr(&expr.right);
+    }
+
+    fn visit_map_expr(&mut self, expr: &Map) {
+        for (key, value) in &expr.entries {
+            self.resolve_expr(key);
+            self.resolve_expr(value);
+        }
+    }
+
+    fn visit_set_expr(&mut self, expr: &Set) {
+        self.resolve_expr(&expr.value);
+        self.resolve_expr(&expr.object);
+    }
+
+    fn visit_set_index_expr(&mut self, expr: &SetIndex) {
+        self.resolve_expr(&expr.value);
+        self.resolve_expr(&expr.object);
+        self.resolve_expr(&expr.index);
+    }
+
+    fn visit_super_expr(&mut self, expr: &Super) {
+        match self.current_class {
+            ClassType::None => self.error(&expr.keyword, "Can't use 'super' outside of a class."),
+            ClassType::Class => self.error(&expr.keyword, "Can't use 'super' in a class with no superclass."),
+            ClassType::Subclass => {}
+        }
+
+        self.resolve_local(expr.id, &expr.keyword);
+    }
+
+    fn visit_this_expr(&mut self, expr: &This) {
+        if self.current_class == ClassType::None {
+            self.error(&expr.keyword, "Can't use 'this' outside of a class.");
+            return;
+        }
+
+        self.resolve_local(expr.id, &expr.keyword);
+    }
+
+    fn visit_unary_expr(&mut self, expr: &Unary) {
+        self.resolve_expr(&expr.right);
+    }
+
+    fn visit_variable_expr(&mut self, expr: &Variable) {
+        let declared_only = self
+            .scopes
+            .last()
+            .and_then(|scope| scope.get(&expr.name.lexeme))
+            .is_some_and(|defined| !defined);
+
+        if declared_only {
+            self.error(&expr.name, "Can't read local variable in its own initializer.");
+        }
+
+        self.resolve_local(expr.id, &expr.name);
+    }
+}
+
+impl StmtVisitor<()> for Resolver<'_> {
+    fn visit_block_stmt(&mut self, stmt: &Block) {
+        self.begin_scope();
+        self.resolve(&stmt.statements);
+        self.end_scope();
+    }
+
+    fn visit_class_stmt(&mut self, stmt: &Class) {
+        let enclosing_class = self.current_class;
+        self.current_class = ClassType::Class;
+
+        self.declare(&stmt.name);
+        self.define(&stmt.name);
+
+        if let Some(superclass) = &stmt.superclass {
+            if superclass.name.lexeme == stmt.name.lexeme {
+                self.error(&superclass.name, "A class can't inherit from itself.");
+            }
+
+            self.current_class = ClassType::Subclass;
+            self.visit_variable_expr(superclass);
+
+            self.begin_scope();
+            self.define_name(&Symbol::intern("super"));
+        }
+
+        self.begin_scope();
+        self.define_name(&Symbol::intern("this"));
+
+        for method in &stmt.methods {
+            let declaration = if method.name.lexeme == "init" {
+                FunctionType::Initializer
+            } else {
+                FunctionType::Method
+            };
+
+            self.resolve_function(method, declaration);
+        }
+
+        self.end_scope();
+
+        if stmt.superclass.is_some() {
+            self.end_scope();
+        }
+
+        self.current_class = enclosing_class;
+    }
+
+    fn visit_expression_stmt(&mut self, stmt: &Expression) {
+        self.resolve_expr(&stmt.expression);
+    }
+
+    fn visit_function_stmt(&mut self, stmt: &Rc<Function>) {
+        // Defined before the body is resolved so the function can recurse.
+        self.declare(&stmt.name);
+        self.define(&stmt.name);
+
+        self.resolve_function(stmt, FunctionType::Function);
+    }
+
+    fn visit_if_stmt(&mut self, stmt: &If) {
+        self.resolve_expr(&stmt.condition);
+        self.resolve_stmt(&stmt.then_branch);
+
+        if let Some(else_branch) = &stmt.else_branch {
+            self.resolve_stmt(else_branch);
+        }
+    }
+
+    fn visit_print_stmt(&mut self, stmt: &Print) {
+        self.resolve_expr(&stmt.expression);
+    }
+
+    fn visit_return_stmt(&mut self, stmt: &Return) {
+        if self.current_function == FunctionType::None {
+            self.error(&stmt.keyword, "Can't return from top-level code.");
+        }
+
+        if let Some(value) = &stmt.value {
+            if self.current_function == FunctionType::Initializer {
+                self.error(&stmt.keyword, "Can't return a value from an initializer.");
+            }
+
+            self.resolve_expr(value);
+        }
+    }
+
+    fn visit_var_stmt(&mut self, stmt: &Var) {
+        self.declare(&stmt.name);
+
+        if let Some(initializer) = &stmt.initializer {
+            self.resolve_expr(initializer);
+        }
+
+        self.define(&stmt.name);
+    }
+
+    fn visit_while_stmt(&mut self, stmt: &While) {
+        self.resolve_expr(&stmt.condition);
+        self.resolve_stmt(&stmt.body);
+    }
+}
+
+impl Resolver<'_> {
+    pub fn new(interpreter: &mut Interpreter) -> Resolver<'_> {
+        Resolver {
+            interpreter,
+            scopes: Vec::new(),
+            current_function: FunctionType::None,
+            current_class: ClassType::None,
+            errors: Vec::new(),
+        }
+    }
+
+    pub fn resolve_program(&mut self, stmts: &[Stmt]) -> Result<(), Vec<ResolveError>> {
+        self.resolve(stmts);
+
+        if self.errors.is_empty() {
+            Ok(())
+        } else {
+            Err(std::mem::take(&mut self.errors))
+        }
+    }
+
+    fn resolve(&mut self, stmts: &[Stmt]) {
+        for stmt in stmts {
+            self.resolve_stmt(stmt);
+        }
+    }
+
+    fn resolve_stmt(&mut self, stmt: &Stmt) {
+        stmt.accept(self)
+    }
+
+    fn resolve_expr(&mut self, expr: &Expr) {
+        expr.accept(self)
+    }
+
+    fn resolve_function(&mut self, function: &Function, function_type: FunctionType) {
+        let enclosing_function = self.current_function;
+        self.current_function = function_type;
+
+        self.begin_scope();
+        for param in &function.params {
+            self.declare(param);
+            self.define(param);
+        }
+        self.resolve(&function.body);
+        self.end_scope();
+
+        self.current_function = enclosing_function;
+    }
+
+    // Anything not found in a local scope is left unresolved and treated as a global.
+    fn resolve_local(&mut self, id: ExprId, name: &Token) {
+        for (depth, scope) in self.scopes.iter().rev().enumerate() {
+            if scope.contains_key(&name.lexeme) {
+                self.interpreter.resolve(id, depth);
+                return;
+            }
+        }
+    }
+
+    fn begin_scope(&mut self) {
+        self.scopes.push(HashMap::new());
+    }
+
+    fn end_scope(&mut self) {
+        self.scopes.pop();
+    }
+
+    fn declare(&mut self, name: &Token) {
+        let already_declared = match self.scopes.last_mut() {
+            Some(scope) => scope.insert(name.lexeme.clone(), false).is_some(),
+            None => return,
+        };
+
+        if already_declared {
+            self.error(name, "Already a variable with this name in this scope.");
+        }
+    }
+
+    fn define(&mut self, name: &Token) {
+        self.define_name(&name.lexeme);
+    }
+
+    fn define_name(&mut self, name: &Symbol) {
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert(name.clone(), true);
+        }
+    }
+
+    fn error(&mut self, token: &Token, message: &str) {
+        self.errors.push(ResolveError::new(token.clone(), message));
+    }
+}
@@ -1,19 +1,37 @@
-use crate::error::LoxError;
-use crate::token::Token;
+use crate::error::ScanError;
+use crate::symbol::Symbol;
+use crate::token::{Position, Span, Token};
 use crate::token_type::{KeyWord, LiteralValue, TokenType};
 use std::collections::HashMap;
-use std::fmt::Display;
-use crate::util::Utils;
-
+use std::io::BufRead;
+
+// Produces tokens on demand, ending with a single `Eof`. Errors are yielded
+// in place of the offending token and scanning carries on after them.
+//
+// `start` and `current` are byte offsets into the source. Columns count
+// characters, so the scanner keeps track of them as it goes instead of
+// measuring the line again for every token.
+//
+// A scanner made `from_reader` reads a line at a time as it runs out of text
+// and forgets what it has already turned into tokens.
 pub struct Scanner {
     source: String,
-    tokens: Vec<Token>,
+    // The offset of `source` in the whole input, everything before it has
+    // been scanned and dropped.
+    offset: usize,
+    input: Option<Box<dyn BufRead>>,
+    // Why the input stopped early, reported once the text before it is scanned.
+    read_error: Option<String>,
 
     start: usize,
     current: usize,
     line: usize,
-
-    has_error: bool,
+    start_line: usize,
+    start_column: usize,
+    current_column: usize,
+    finished: bool,
+    // One entry per `${` still open, counting the braces opened inside it.
+    interpolations: Vec<usize>,
 
     keywords: HashMap<&'static str, TokenType>,
 }
@@ -22,153 +40,347 @@ impl Scanner {
     pub fn new(source: String) -> Self {
         Self {
             source,
-            tokens: vec![],
+            offset: 0,
+            input: None,
+            read_error: None,
             start: 0,
             current: 0,
             line: 1,
-            has_error: false,
+            start_line: 1,
+            start_column: 0,
+            current_column: 0,
+            finished: false,
+            interpolations: Vec::new(),
 
             keywords: KeyWord::make_keywords(),
         }
     }
 
-    pub fn get_tokens(self) -> Vec<Token> {
-        self.tokens
+    pub fn from_reader(input: impl BufRead + 'static) -> Self {
+        let mut scanner = Scanner::new(String::new());
+        scanner.input = Some(Box::new(input));
+        scanner.refill();
+        scanner
+    }
+
+    // Scans the whole source up front, for callers that need every token.
+    pub fn scan_tokens(self) -> (Vec<Token>, Vec<ScanError>) {
+        let mut tokens = Vec::new();
+        let mut errors = Vec::new();
+
+        for result in self {
+            match result {
+                Ok(token) => tokens.push(token),
+                Err(err) => errors.push(err),
+            }
+        }
+
+        (tokens, errors)
     }
 
     fn is_at_end(&self) -> bool {
-        self.current >= self.source.char_indices().count()
+        self.current >= self.offset + self.source.len()
     }
 
+    // Reading more as soon as the text runs out means `peek` always sees the
+    // next character. Lines keep their `\n`, so nothing looks further ahead
+    // than the line it is on.
     fn advance(&mut self) -> Option<char> {
-        let c = self.source.char_indices().nth(self.current);
-        self.current += 1;
-        match c {
-            None => {
-                None
-            }
-            Some(val) => {
-                Some(val.1)
+        let c = self.rest().chars().next()?;
+        self.current += c.len_utf8();
+        self.current_column += 1;
+        if self.is_at_end() {
+            self.refill();
+        }
+        Some(c)
+    }
+
+    // Keeps the token in progress and appends the next line of the input.
+    fn refill(&mut self) {
+        let Some(input) = self.input.as_mut() else {
+            return;
+        };
+
+        self.source.drain(..self.start - self.offset);
+        self.offset = self.start;
+        match input.read_line(&mut self.source) {
+            Ok(0) => self.input = None,
+            Ok(_) => {}
+            Err(err) => {
+                self.input = None;
+                self.read_error = Some(err.to_string());
             }
         }
     }
 
-    fn add_token(&mut self, token: TokenType, literal: Option<LiteralValue>) {
-        let text = Utils::get_char_range(&self.source, self.start, self.current);
-        self.tokens
-            .push(Token::new(token, text, literal, self.line));
+    fn text(&self, start: usize, end: usize) -> &str {
+        &self.source[start - self.offset..end - self.offset]
     }
 
-    fn match_next(&mut self, expected: &char) -> bool {
-        if self.is_at_end() {
-            return false;
+    fn rest(&self) -> &str {
+        &self.source[self.current - self.offset..]
+    }
+
+    fn newline(&mut self) {
+        self.line += 1;
+        self.current_column = 0;
+    }
+
+    fn add_token(&self, token: TokenType, literal: Option<LiteralValue>) -> Option<Result<Token, ScanError>> {
+        let text = self.text(self.start, self.current);
+        let (start, end, span) = (self.start_position(), self.end_position(), self.span());
+        Some(Ok(Token::new(token, Symbol::intern(text), literal, start, end, span)))
+    }
+
+    fn column(&self) -> usize {
+        self.start_column + 1
+    }
+
+    fn start_position(&self) -> Position {
+        Position {
+            line: self.start_line,
+            column: self.column(),
+        }
+    }
+
+    // Just past the last character scanned, string literals may have moved
+    // on to a later line than the one they started on.
+    fn end_position(&self) -> Position {
+        Position {
+            line: self.line,
+            column: self.current_column + 1,
+        }
+    }
+
+    fn span(&self) -> Span {
+        Span {
+            start: self.start,
+            end: self.current,
         }
-        if self.source.chars().nth(self.current).unwrap() != *expected {
+    }
+
+    fn match_next(&mut self, expected: char) -> bool {
+        if self.peek() != expected || self.is_at_end() {
             return false;
         }
 
-        self.current += 1;
+        self.advance();
         true
     }
 
-    fn peek(&self) -> Option<char> {
-        if self.is_at_end() {
-            return Some('\0');
-        }
-        Some(self.source.char_indices().nth(self.current).unwrap().1)
+    // Both return '\0' past the end of the source.
+    fn peek(&self) -> char {
+        self.rest().chars().next().unwrap_or('\0')
     }
 
-    fn peek_next(&self) -> Option<char> {
-        if self.current + 1 > self.source.len() {
-            return Some('\0');
-        }
-        self.source.chars().nth(self.current + 1)
+    fn peek_next(&self) -> char {
+        self.rest().chars().nth(1).unwrap_or('\0')
     }
 
-    fn string(&mut self) {
-        while self.peek().unwrap() != '"' && !self.is_at_end() {
-            self.advance();
+    // Keeps going after a bad escape so the rest of the literal is skipped,
+    // then reports the first one. `resumed` is set for the rest of a literal
+    // after an interpolation.
+    fn string(&mut self, resumed: bool) -> Option<Result<Token, ScanError>> {
+        let (interpolation, end) = if resumed {
+            (TokenType::InterpolationMiddle, TokenType::InterpolationEnd)
+        } else {
+            (TokenType::Interpolation, TokenType::String)
+        };
+        let mut value = String::new();
+        let mut error = None;
+
+        loop {
+            match self.advance() {
+                // Any `${` still open is part of the same runaway literal, so
+                // it isn't reported again at the end of the input.
+                None => {
+                    self.interpolations.clear();
+                    return Some(Err(self.unterminated_string()))
+                }
+                Some('"') => break,
+                Some('$') if self.match_next('{') => {
+                    self.interpolations.push(0);
+                    return match error {
+                        Some(err) => Some(Err(err)),
+                        None => self.add_token(interpolation, Some(LiteralValue::String(Symbol::from(value)))),
+                    };
+                }
+                Some('\\') => match self.escape() {
+                    Ok(c) => value.push(c),
+                    Err(err) => {
+                        error.get_or_insert(err);
+                    }
+                },
+                Some('\n') => {
+                    self.newline();
+                    value.push('\n');
+                }
+                Some(c) => value.push(c),
+            }
         }
 
-        if self.is_at_end() {
-            LoxError::unterminated_string(self.line);
-            self.has_error = true;
-            return;
+        match error {
+            Some(err) => Some(Err(err)),
+            None => self.add_token(end, Some(LiteralValue::String(Symbol::from(value)))),
         }
+    }
 
-        self.advance();
+    // Called with the backslash already consumed.
+    fn escape(&mut self) -> Result<char, ScanError> {
+        let (start, column) = (self.current - 1, self.current_column);
+
+        let c = match self.advance() {
+            Some('n') => Some('\n'),
+            Some('t') => Some('\t'),
+            Some('r') => Some('\r'),
+            Some('0') => Some('\0'),
+            Some('"') => Some('"'),
+            Some('$') => Some('$'),
+            Some('\\') => Some('\\'),
+            Some('u') if self.match_next('{') => {
+                let digits = self.current;
+                while self.peek().is_ascii_hexdigit() {
+                    self.advance();
+                }
+                let hex = self.text(digits, self.current);
+                let code = u32::from_str_radix(hex, 16).ok().filter(|_| hex.len() <= 6);
+
+                // Take in the rest of a malformed escape so the error shows all of it.
+                let end = self.current;
+                while !matches!(self.peek(), '}' | '"' | '\n') && !self.is_at_end() {
+                    self.advance();
+                }
+
+                if self.current == end && self.match_next('}') {
+                    code.and_then(char::from_u32)
+                } else {
+                    self.match_next('}');
+                    None
+                }
+            }
+            Some('\n') => {
+                self.newline();
+                None
+            }
+            _ => None,
+        };
 
-        let value = Utils::get_char_range(&self.source, self.start + 1, self.current - 1);
-        self.add_token(TokenType::String, Option::from(LiteralValue::String(value)));
+        c.ok_or_else(|| {
+            let lexeme = self.text(start, self.current).to_string();
+            let span = Span {
+                start,
+                end: self.current,
+            };
+            ScanError::invalid_escape(lexeme, self.line, column, span)
+        })
     }
 
-    fn number(&mut self) {
-        while self.peek().unwrap().is_ascii_digit() {
+    // Reported on the last line the literal has text on, pointing at the part
+    // of the literal on that line.
+    fn unterminated_string(&self) -> ScanError {
+        let text = self.text(self.start, self.current);
+        let body = text.trim_end_matches(['\r', '\n']);
+        let line = self.line - text[body.len()..].matches('\n').count();
+        let (lexeme, column) = match body.rfind('\n') {
+            Some(newline) => (&body[newline + 1..], 1),
+            None => (body, self.column()),
+        };
+
+        let start = self.start + body.len() - lexeme.len();
+        let span = Span {
+            start,
+            end: start + lexeme.len(),
+        };
+        ScanError::unterminated_string(lexeme.to_string(), line, column, span)
+    }
+
+    // Block comments nest, so commenting out a region that already contains
+    // one works as expected.
+    fn block_comment(&mut self) -> Option<Result<Token, ScanError>> {
+        let (line, column) = (self.line, self.column());
+        let mut depth = 1;
+
+        while depth > 0 {
+            match self.advance() {
+                None => return Some(Err(ScanError::unterminated_comment(line, column, self.start))),
+                Some('/') if self.match_next('*') => depth += 1,
+                Some('*') if self.match_next('/') => depth -= 1,
+                Some('\n') => self.newline(),
+                Some(_) => {}
+            }
+        }
+
+        None
+    }
+
+    fn number(&mut self) -> Option<Result<Token, ScanError>> {
+        while self.peek().is_ascii_digit() {
             self.advance();
         }
 
-        if self.peek().unwrap() == '.' && self.peek_next().unwrap().is_ascii_digit() {
+        if self.peek() == '.' && self.peek_next().is_ascii_digit() {
             self.advance();
 
-            while self.peek().unwrap().is_ascii_digit() {
+            while self.peek().is_ascii_digit() {
                 self.advance();
             }
         }
 
-        let value = self.source[self.start..self.current]
-            .to_owned()
-            .parse::<f64>()
-            .unwrap();
+        let value = self.text(self.start, self.current).parse::<f64>().unwrap();
 
-        self.add_token(TokenType::Number, Some(LiteralValue::Number(value)));
+        self.add_token(TokenType::Number, Some(LiteralValue::Number(value)))
     }
 
-    fn identifier(&mut self) {
-        while (self.peek().unwrap().is_ascii_alphanumeric() || (self.peek().unwrap() == '_'))
-            && !self.is_at_end()
-        {
+    fn identifier(&mut self) -> Option<Result<Token, ScanError>> {
+        while self.peek().is_ascii_alphanumeric() || self.peek() == '_' {
             self.advance();
         }
 
-        let value = self.source[self.start..self.current].to_owned();
-        let token_type = self.keywords.get(value.as_str()).cloned();
+        let token_type = self.keywords.get(self.text(self.start, self.current)).cloned();
 
-        match token_type {
-            Some(token_type) => {
-                self.add_token(token_type, None);
-            }
-            None => {
-                self.add_token(TokenType::Identifier, None);
-            }
-        }
+        self.add_token(token_type.unwrap_or(TokenType::Identifier), None)
     }
 
-    fn scan_token(&mut self) {
-        let c = match self.advance() {
-            Some(c) => c,
-            None => {
-                println!("SCAN_TOKEN OUT OF BOUNDS");
-
-                return;
-            }
-        };
+    // Returns None for whitespace and comments.
+    fn scan_token(&mut self) -> Option<Result<Token, ScanError>> {
+        let c = self.advance()?;
 
         match c {
             '(' => self.add_token(TokenType::LeftParan, None),
             ')' => self.add_token(TokenType::RightParan, None),
-            '{' => self.add_token(TokenType::LeftBrace, None),
-            '}' => self.add_token(TokenType::RightBrace, None),
+            '{' => {
+                if let Some(depth) = self.interpolations.last_mut() {
+                    *depth += 1;
+                }
+                self.add_token(TokenType::LeftBrace, None)
+            }
+            '}' => match self.interpolations.last_mut() {
+                // Closes the interpolation, the string literal carries on.
+                Some(0) => {
+                    self.interpolations.pop();
+                    self.string(true)
+                }
+                Some(depth) => {
+                    *depth -= 1;
+                    self.add_token(TokenType::RightBrace, None)
+                }
+                None => self.add_token(TokenType::RightBrace, None),
+            },
+
+            '[' => self.add_token(TokenType::LeftBracket, None),
+            ']' => self.add_token(TokenType::RightBracket, None),
 
             '+' => self.add_token(TokenType::Plus, None),
             '-' => self.add_token(TokenType::Minus, None),
             '*' => self.add_token(TokenType::Star, None),
 
             ',' => self.add_token(TokenType::Comma, None),
+            ':' => self.add_token(TokenType::Colon, None),
             '.' => self.add_token(TokenType::Dot, None),
             ';' => self.add_token(TokenType::Semicolon, None),
 
             '=' => {
-                let is_equal = if self.match_next(&'=') {
+                let is_equal = if self.match_next('=') {
                     TokenType::EqualEqual
                 } else {
                     TokenType::Equal
@@ -176,7 +388,7 @@ impl Scanner {
                 self.add_token(is_equal, None)
             }
             '!' => {
-                let is_bang = if self.match_next(&'=') {
+                let is_bang = if self.match_next('=') {
                     TokenType::BangEqual
                 } else {
                     TokenType::Bang
@@ -184,7 +396,7 @@ impl Scanner {
                 self.add_token(is_bang, None)
             }
             '<' => {
-                let is_less = if self.match_next(&'=') {
+                let is_less = if self.match_next('=') {
                     TokenType::LessEqual
                 } else {
                     TokenType::Less
@@ -192,7 +404,7 @@ impl Scanner {
                 self.add_token(is_less, None)
             }
             '>' => {
-                let is_greater = if self.match_next(&'=') {
+                let is_greater = if self.match_next('=') {
                     TokenType::GreaterEqual
                 } else {
                     TokenType::Greater
@@ -201,57 +413,68 @@ impl Scanner {
             }
 
             '/' => {
-                let is_slash = if self.match_next(&'/') {
-                    while self.peek().unwrap_or('\0') != '\n' && !self.is_at_end() {
+                let is_slash = if self.match_next('/') {
+                    while self.peek() != '\n' && !self.is_at_end() {
                         self.advance();
                     }
-                    return;
+                    return None;
+                } else if self.match_next('*') {
+                    return self.block_comment();
                 } else {
                     TokenType::Slash
                 };
                 self.add_token(is_slash, None)
             }
 
-            '"' => self.string(),
+            '"' => self.string(false),
 
             '0'..='9' => self.number(),
 
             'a'..='z' | 'A'..='Z' | '_' => self.identifier(),
 
-            '\n' => self.line += 1,
-            ' ' | '\r' | '\t' => {} // Ignore whitespace
+            '\n' => {
+                self.newline();
+                None
+            }
+            ' ' | '\r' | '\t' => None, // Ignore whitespace
             _ => {
                 // Handle unexpected characters
-                LoxError::unexpected_character(self.line, c);
-                self.has_error = true;
+                Some(Err(ScanError::unexpected_character(c, self.line, self.column(), self.span())))
             }
         }
     }
+}
+
+impl Iterator for Scanner {
+    type Item = Result<Token, ScanError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.finished {
+            return None;
+        }
 
-    pub fn scan_tokens(&mut self) {
         while !self.is_at_end() {
             self.start = self.current;
-            self.scan_token();
+            self.start_line = self.line;
+            self.start_column = self.current_column;
+            if let Some(result) = self.scan_token() {
+                return Some(result);
+            }
         }
 
-        self.tokens
-            .push(Token::new(TokenType::EOF, "".to_string(), None, self.line));
-    }
-
-    pub fn has_error(&self) -> bool {
-        self.has_error
-    }
-}
-
-impl Display for Scanner {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        Ok(for token in self.tokens.iter() {
-            let literal_str = match &token.literal {
-                Some(literal) => format!("{}", literal),
-                None => "null".to_string(),
-            };
+        self.start = self.current;
+        self.start_line = self.line;
+        self.start_column = self.current_column;
+        if let Some(reason) = self.read_error.take() {
+            return Some(Err(ScanError::unreadable(reason, self.line, self.column(), self.current)));
+        }
+        if !self.interpolations.is_empty() {
+            self.interpolations.clear();
+            return Some(Err(self.unterminated_string()));
+        }
 
-            writeln!(f, "{} {} {}", token.token_type, token.lexeme, literal_str)?;
-        })
+        self.finished = true;
+        let (start, span) = (self.start_position(), self.span());
+        Some(Ok(Token::new(TokenType::Eof, Symbol::intern(""), None, start, start, span)))
     }
 }
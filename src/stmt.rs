@@ -1,18 +1,28 @@
-use crate::expr::Expr;
+use std::rc::Rc;
+use crate::expr::{Expr, Variable};
 use crate::token::Token;
-use crate::token_type::LiteralValue;
 
 #[derive(Clone, Debug)]
 pub enum Stmt {
     Block(Block),
+    Class(Class),
     Expression(Expression),
+    Function(Rc<Function>),
+    If(If),
     Print(Print),
+    Return(Return),
     Var(Var),
+    While(While),
 }
 
 #[derive(Clone, Debug)]
 pub struct Block {
-    pub statements: Vec<Box<Stmt>>
+    pub statements: Vec<Stmt>
+}
+
+#[derive(Clone, Debug)]
+pub struct Class {
+    pub name: Token, pub superclass: Option<Variable>, pub methods: Vec<Rc<Function>>
 }
 
 #[derive(Clone, Debug)]
@@ -20,30 +30,60 @@ pub struct Expression {
     pub expression: Box<Expr>
 }
 
+#[derive(Clone, Debug)]
+pub struct Function {
+    pub name: Token, pub params: Vec<Token>, pub body: Vec<Stmt>
+}
+
+#[derive(Clone, Debug)]
+pub struct If {
+    pub condition: Box<Expr>, pub then_branch: Box<Stmt>, pub else_branch: Option<Box<Stmt>>
+}
+
 #[derive(Clone, Debug)]
 pub struct Print {
     pub expression: Box<Expr>
 }
 
+#[derive(Clone, Debug)]
+pub struct Return {
+    pub keyword: Token, pub value: Option<Box<Expr>>
+}
+
 #[derive(Clone, Debug)]
 pub struct Var {
     pub name: Token, pub initializer: Option<Box<Expr>>
 }
 
+#[derive(Clone, Debug)]
+pub struct While {
+    pub condition: Box<Expr>, pub body: Box<Stmt>
+}
+
 pub trait Visitor<R> {
-    fn visit_block_stmt(&mut self, expr: &Block) -> R;
-    fn visit_expression_stmt(&mut self, expr: &Expression) -> R;
-    fn visit_print_stmt(&mut self, expr: &Print) -> R;
-    fn visit_var_stmt(&mut self, expr: &Var) -> R;
+    fn visit_block_stmt(&mut self, stmt: &Block) -> R;
+    fn visit_class_stmt(&mut self, stmt: &Class) -> R;
+    fn visit_expression_stmt(&mut self, stmt: &Expression) -> R;
+    fn visit_function_stmt(&mut self, stmt: &Rc<Function>) -> R;
+    fn visit_if_stmt(&mut self, stmt: &If) -> R;
+    fn visit_print_stmt(&mut self, stmt: &Print) -> R;
+    fn visit_return_stmt(&mut self, stmt: &Return) -> R;
+    fn visit_var_stmt(&mut self, stmt: &Var) -> R;
+    fn visit_while_stmt(&mut self, stmt: &While) -> R;
 }
 
 impl Stmt {
     pub fn accept<V: Visitor<R>, R>(&self, visitor: &mut V) -> R {
         match self {
             Stmt::Block(stmt) => visitor.visit_block_stmt(stmt),
+            Stmt::Class(stmt) => visitor.visit_class_stmt(stmt),
             Stmt::Expression(stmt) => visitor.visit_expression_stmt(stmt),
+            Stmt::Function(stmt) => visitor.visit_function_stmt(stmt),
+            Stmt::If(stmt) => visitor.visit_if_stmt(stmt),
             Stmt::Print(stmt) => visitor.visit_print_stmt(stmt),
+            Stmt::Return(stmt) => visitor.visit_return_stmt(stmt),
             Stmt::Var(stmt) => visitor.visit_var_stmt(stmt),
+            Stmt::While(stmt) => visitor.visit_while_stmt(stmt),
         }
     }
 }
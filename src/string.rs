@@ -0,0 +1,151 @@
+use crate::error::NativeError;
+use crate::native::{self, NativeFunction, Runtime};
+use crate::token_type::LiteralValue;
+
+// The methods of a string, `"a,b".split(",")` and so on. They are natives
+// called with the string in front of the arguments, `arity` only counts the
+// arguments. Positions and lengths are in characters, not bytes.
+pub fn method<R: Runtime>(name: &str) -> Option<NativeFunction<R>> {
+    let method = match name {
+        "ends_with" => NativeFunction::new("ends_with", 1, ends_with),
+        "index_of" => NativeFunction::new("index_of", 1, index_of),
+        "join" => NativeFunction::new("join", 1, join),
+        "len" => NativeFunction::new("len", 0, len),
+        "lower" => NativeFunction::new("lower", 0, lower),
+        "replace" => NativeFunction::new("replace", 2, replace),
+        "split" => NativeFunction::new("split", 1, split),
+        "starts_with" => NativeFunction::new("starts_with", 1, starts_with),
+        "substring" => NativeFunction::new("substring", 2, substring),
+        "trim" => NativeFunction::new("trim", 0, trim),
+        "upper" => NativeFunction::new("upper", 0, upper),
+        _ => return None,
+    };
+    Some(method)
+}
+
+// What `string[index]` evaluates to.
+pub fn char_at(string: &str, index: Option<f64>) -> Result<char, String> {
+    match index {
+        Some(index) if index.fract() == 0.0 => {
+            let len = string.chars().count();
+            if index >= 0.0 && (index as usize) < len {
+                Ok(string.chars().nth(index as usize).expect("index is in bounds"))
+            } else {
+                Err(format!("String index {} out of bounds for length {}.", index, len))
+            }
+        }
+        _ => Err("String index must be an integer.".to_string()),
+    }
+}
+
+fn ends_with<R: Runtime>(runtime: &mut R, arguments: &[R::Value]) -> Result<R::Value, NativeError> {
+    let ends_with = receiver(runtime, &arguments[0]).ends_with(string(runtime, "ends_with", &arguments[1])?);
+    Ok(runtime.literal(LiteralValue::Bool(ends_with)))
+}
+
+// -1 when the string doesn't occur.
+fn index_of<R: Runtime>(runtime: &mut R, arguments: &[R::Value]) -> Result<R::Value, NativeError> {
+    let haystack = receiver(runtime, &arguments[0]);
+    let needle = string(runtime, "index_of", &arguments[1])?;
+    let index = match haystack.find(needle) {
+        Some(byte) => haystack[..byte].chars().count() as f64,
+        None => -1.0,
+    };
+    Ok(runtime.literal(LiteralValue::Number(index)))
+}
+
+// `", ".join(list)`, elements that aren't strings are shown the way `print`
+// would show them.
+fn join<R: Runtime>(runtime: &mut R, arguments: &[R::Value]) -> Result<R::Value, NativeError> {
+    let separator = receiver(runtime, &arguments[0]);
+    let elements = runtime
+        .list(&arguments[1])
+        .ok_or_else(|| native::argument_type(runtime, "join", "a list", &arguments[1]))?;
+
+    let joined = elements
+        .iter()
+        .map(|element| runtime.stringify(element))
+        .collect::<Vec<_>>()
+        .join(separator);
+    Ok(runtime.literal(LiteralValue::String(joined.into())))
+}
+
+fn len<R: Runtime>(runtime: &mut R, arguments: &[R::Value]) -> Result<R::Value, NativeError> {
+    let len = receiver(runtime, &arguments[0]).chars().count();
+    Ok(runtime.literal(LiteralValue::Number(len as f64)))
+}
+
+fn lower<R: Runtime>(runtime: &mut R, arguments: &[R::Value]) -> Result<R::Value, NativeError> {
+    let lower = receiver(runtime, &arguments[0]).to_lowercase();
+    Ok(runtime.literal(LiteralValue::String(lower.into())))
+}
+
+// Replaces every occurrence.
+fn replace<R: Runtime>(runtime: &mut R, arguments: &[R::Value]) -> Result<R::Value, NativeError> {
+    let from = string(runtime, "replace", &arguments[1])?;
+    let to = string(runtime, "replace", &arguments[2])?;
+    let replaced = receiver(runtime, &arguments[0]).replace(from, to);
+    Ok(runtime.literal(LiteralValue::String(replaced.into())))
+}
+
+// An empty separator splits the string into its characters.
+fn split<R: Runtime>(runtime: &mut R, arguments: &[R::Value]) -> Result<R::Value, NativeError> {
+    let string_value = receiver(runtime, &arguments[0]);
+    let separator = string(runtime, "split", &arguments[1])?;
+    let parts: Vec<String> = if separator.is_empty() {
+        string_value.chars().map(String::from).collect()
+    } else {
+        string_value.split(separator).map(String::from).collect()
+    };
+
+    let parts = parts
+        .into_iter()
+        .map(|part| runtime.literal(LiteralValue::String(part.into())))
+        .collect();
+    Ok(runtime.alloc_list(parts))
+}
+
+fn starts_with<R: Runtime>(runtime: &mut R, arguments: &[R::Value]) -> Result<R::Value, NativeError> {
+    let starts_with = receiver(runtime, &arguments[0]).starts_with(string(runtime, "starts_with", &arguments[1])?);
+    Ok(runtime.literal(LiteralValue::Bool(starts_with)))
+}
+
+// The characters from `start` up to but not including `end`.
+fn substring<R: Runtime>(runtime: &mut R, arguments: &[R::Value]) -> Result<R::Value, NativeError> {
+    let string_value = receiver(runtime, &arguments[0]);
+    let start = native::number(runtime, "substring", &arguments[1])?;
+    let end = native::number(runtime, "substring", &arguments[2])?;
+
+    let len = string_value.chars().count();
+    let in_bounds = start.fract() == 0.0 && end.fract() == 0.0 && 0.0 <= start && start <= end && end <= len as f64;
+    if !in_bounds {
+        return Err(NativeError::Range {
+            function: "substring",
+            message: format!("range {}..{} is out of bounds for length {}", start, end, len),
+        });
+    }
+
+    let substring: String = string_value.chars().skip(start as usize).take((end - start) as usize).collect();
+    Ok(runtime.literal(LiteralValue::String(substring.into())))
+}
+
+fn trim<R: Runtime>(runtime: &mut R, arguments: &[R::Value]) -> Result<R::Value, NativeError> {
+    let trimmed = receiver(runtime, &arguments[0]).trim().to_string();
+    Ok(runtime.literal(LiteralValue::String(trimmed.into())))
+}
+
+fn upper<R: Runtime>(runtime: &mut R, arguments: &[R::Value]) -> Result<R::Value, NativeError> {
+    let upper = receiver(runtime, &arguments[0]).to_uppercase();
+    Ok(runtime.literal(LiteralValue::String(upper.into())))
+}
+
+// Methods are only looked up on strings.
+fn receiver<'a, R: Runtime>(runtime: &'a R, value: &'a R::Value) -> &'a str {
+    runtime.string(value).expect("string methods are called on strings")
+}
+
+fn string<'a, R: Runtime>(runtime: &'a R, function: &'static str, value: &'a R::Value) -> Result<&'a str, NativeError> {
+    runtime
+        .string(value)
+        .ok_or_else(|| native::argument_type(runtime, function, "a string", value))
+}
@@ -0,0 +1,124 @@
+use std::cell::RefCell;
+use std::cmp::Ordering;
+use std::collections::HashSet;
+use std::fmt::{self, Debug, Display, Formatter};
+use std::hash::{Hash, Hasher};
+use std::ops::Deref;
+use std::rc::Rc;
+
+// An interned string. Every symbol with the same text shares one allocation,
+// so comparing and hashing them only looks at the pointer.
+#[derive(Clone)]
+pub struct Symbol(Rc<str>);
+
+// The table only keeps strings alive while something else still refers to
+// them, dead entries are dropped whenever it has doubled in size.
+struct Interner {
+    strings: HashSet<Rc<str>>,
+    next_prune: usize,
+}
+
+const MIN_PRUNE: usize = 1024;
+
+thread_local! {
+    static INTERNER: RefCell<Interner> = RefCell::new(Interner {
+        strings: HashSet::new(),
+        next_prune: MIN_PRUNE,
+    });
+}
+
+impl Symbol {
+    pub fn intern(string: &str) -> Symbol {
+        INTERNER.with(|interner| {
+            let mut interner = interner.borrow_mut();
+            if let Some(existing) = interner.strings.get(string) {
+                return Symbol(Rc::clone(existing));
+            }
+
+            if interner.strings.len() >= interner.next_prune {
+                interner.strings.retain(|string| Rc::strong_count(string) > 1);
+                interner.next_prune = MIN_PRUNE.max(interner.strings.len() * 2);
+            }
+
+            let string: Rc<str> = Rc::from(string);
+            interner.strings.insert(Rc::clone(&string));
+            Symbol(string)
+        })
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl PartialEq for Symbol {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Eq for Symbol {}
+
+impl Hash for Symbol {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        (Rc::as_ptr(&self.0) as *const u8 as usize).hash(state);
+    }
+}
+
+// Sorting goes by the text, which agrees with equality since equal text
+// always means the same symbol.
+impl PartialOrd for Symbol {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Symbol {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.as_str().cmp(other.as_str())
+    }
+}
+
+impl PartialEq<str> for Symbol {
+    fn eq(&self, other: &str) -> bool {
+        self.as_str() == other
+    }
+}
+
+impl PartialEq<&str> for Symbol {
+    fn eq(&self, other: &&str) -> bool {
+        self.as_str() == *other
+    }
+}
+
+impl Deref for Symbol {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl From<&str> for Symbol {
+    fn from(string: &str) -> Symbol {
+        Symbol::intern(string)
+    }
+}
+
+impl From<String> for Symbol {
+    fn from(string: String) -> Symbol {
+        Symbol::intern(&string)
+    }
+}
+
+impl Display for Symbol {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.pad(&self.0)
+    }
+}
+
+impl Debug for Symbol {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        Debug::fmt(&*self.0, f)
+    }
+}
@@ -1,33 +1,71 @@
+use crate::symbol::Symbol;
 use crate::token_type::{LiteralValue, TokenType};
 use std::fmt;
 use std::fmt::Formatter;
 
+// Byte offsets into the source, `end` being exclusive.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+// A 1-based line and column, columns counted in characters.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Position {
+    pub line: usize,
+    pub column: usize,
+}
+
+// `line` and `column` point at the first character of the lexeme and `end`
+// just past its last one.
 #[derive(Clone, Debug)]
 pub struct Token {
     pub token_type: TokenType,
-    pub lexeme: String,
+    pub lexeme: Symbol,
     pub literal: Option<LiteralValue>,
     pub line: usize,
+    pub column: usize,
+    pub end: Position,
+    pub span: Span,
+}
+
+impl fmt::Display for Position {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.line, self.column)
+    }
 }
 
 impl fmt::Display for Token {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-        write!(f, "[{}]: {} {}", self.line, self.token_type, self.lexeme)
+        write!(f, "[{}]: {} {}", self.start(), self.token_type, self.lexeme)
     }
 }
 
 impl Token {
     pub fn new(
         token_type: TokenType,
-        lexeme: String,
+        lexeme: Symbol,
         literal: Option<LiteralValue>,
-        line: usize,
+        start: Position,
+        end: Position,
+        span: Span,
     ) -> Token {
         Token {
             token_type,
             lexeme,
             literal,
-            line,
+            line: start.line,
+            column: start.column,
+            end,
+            span,
+        }
+    }
+
+    pub fn start(&self) -> Position {
+        Position {
+            line: self.line,
+            column: self.column,
         }
     }
 }
@@ -1,5 +1,13 @@
 use std::collections::HashMap;
 use std::fmt::{Display, Formatter};
+use std::rc::Rc;
+use crate::class::{InstanceRef, LoxClass};
+use crate::function::LoxFunction;
+use crate::interpreter::Interpreter;
+use crate::list::ListRef;
+use crate::map::MapRef;
+use crate::native::NativeFunction;
+use crate::symbol::Symbol;
 
 pub struct KeyWord {}
 
@@ -35,6 +43,8 @@ pub enum TokenType {
     RightParan,
     LeftBrace,
     RightBrace,
+    LeftBracket,
+    RightBracket,
 
     Plus,
     Minus,
@@ -42,6 +52,7 @@ pub enum TokenType {
     Slash,
 
     Comma,
+    Colon,
     Dot,
     Semicolon,
 
@@ -55,6 +66,12 @@ pub enum TokenType {
     GreaterEqual,
 
     String,
+    // The part of a string literal before a `${`, the interpolated tokens
+    // follow and the literal resumes after the matching `}`.
+    Interpolation,
+    // Where it resumes: up to the next `${`, or the rest up to the closing quote.
+    InterpolationMiddle,
+    InterpolationEnd,
     Number,
     Identifier,
 
@@ -75,41 +92,66 @@ pub enum TokenType {
     Var,
     While,
 
-    EOF,
+    Eof,
 }
 
 #[derive(Clone, Debug)]
 pub enum LiteralValue {
-    String(String),
+    String(Symbol),
     Number(f64),
     Bool(bool),
     Nil,
+    Function(Rc<LoxFunction>),
+    Native(Rc<NativeFunction<Interpreter>>),
+    Class(Rc<LoxClass>),
+    Instance(InstanceRef),
+    List(ListRef),
+    Map(MapRef),
 }
 
 impl Display for LiteralValue {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        Ok(match self {
+        match self {
             LiteralValue::String(string) => {
-                write!(f, "{}", string)?;
+                write!(f, "{}", string)
             }
             LiteralValue::Number(num) => {
                 if num.fract() == 0.0 {
-                    write!(f, "{:.1}", num)?;
+                    write!(f, "{:.1}", num)
                 } else {
-                    write!(f, "{}", num)?;
+                    write!(f, "{}", num)
                 }
             }
             LiteralValue::Bool(boolean) => {
                 if *boolean {
-                    write!(f, "true")?;
+                    write!(f, "true")
                 } else {
-                    write!(f, "false")?;
+                    write!(f, "false")
                 }
             }
             LiteralValue::Nil => {
-                write!(f, "nil")?;
+                write!(f, "nil")
             }
-        })
+            LiteralValue::Function(function) => {
+                write!(f, "{}", function)
+            }
+            LiteralValue::Native(native) => {
+                write!(f, "{}", native)
+            }
+            LiteralValue::Class(class) => {
+                write!(f, "{}", class)
+            }
+            LiteralValue::Instance(instance) => {
+                write!(f, "{}", instance)
+            }
+            // The elements live on the interpreter's heap, `Interpreter::stringify` prints them.
+            LiteralValue::List(_) => {
+                write!(f, "<list>")
+            }
+            LiteralValue::Map(_) => {
+                write!(f, "<map>")
+            }
+        }
     }
 }
 
@@ -123,6 +165,8 @@ impl Display for TokenType {
                 TokenType::RightParan => "RIGHT_PAREN",
                 TokenType::LeftBrace => "LEFT_BRACE",
                 TokenType::RightBrace => "RIGHT_BRACE",
+                TokenType::LeftBracket => "LEFT_BRACKET",
+                TokenType::RightBracket => "RIGHT_BRACKET",
 
                 TokenType::Plus => "PLUS",
                 TokenType::Minus => "MINUS",
@@ -130,6 +174,7 @@ impl Display for TokenType {
                 TokenType::Slash => "SLASH",
 
                 TokenType::Comma => "COMMA",
+                TokenType::Colon => "COLON",
                 TokenType::Dot => "DOT",
                 TokenType::Semicolon => "SEMICOLON",
 
@@ -143,6 +188,9 @@ impl Display for TokenType {
                 TokenType::GreaterEqual => "GREATER_EQUAL",
 
                 TokenType::String => "STRING",
+                TokenType::Interpolation => "INTERPOLATION",
+                TokenType::InterpolationMiddle => "INTERPOLATION_MIDDLE",
+                TokenType::InterpolationEnd => "INTERPOLATION_END",
                 TokenType::Number => "NUMBER",
                 TokenType::Identifier => "IDENTIFIER",
 
@@ -163,7 +211,7 @@ impl Display for TokenType {
                 TokenType::Var => "VAR",
                 TokenType::While => "WHILE",
 
-                TokenType::EOF => "EOF",
+                TokenType::Eof => "EOF",
             }
         )
     }
@@ -0,0 +1,966 @@
+use std::collections::HashMap;
+use std::rc::Rc;
+use crate::chunk::{Chunk, OpCode, Program};
+use crate::error::RuntimeError;
+use crate::gc::{GcConfig, GcStats, Trace, Tracer};
+use crate::heap::{BoundMethod, Class, Closure, Heap, Instance, Obj, ObjRef, Upvalue, Value};
+use crate::list::ListMethod;
+use crate::map::{LoxMap, MapKey, MapMethod};
+use crate::math::{self, Rng};
+use crate::native::{self, Runtime, ValueType};
+use crate::string;
+use crate::symbol::Symbol;
+use crate::token_type::LiteralValue;
+use crate::util::Utils;
+
+// Calls nested deeper than this are a stack overflow, in the tree-walker too.
+pub const FRAMES_MAX: usize = 10_000;
+
+// `verify` keeps every instruction inside its frame, but bytecode that pops a
+// captured variable without closing it leaves an upvalue pointing past the
+// stack. Those are caught when the upvalue is used.
+const CORRUPT: &str = "Corrupt bytecode.";
+
+#[derive(Clone, Copy)]
+struct CallFrame {
+    closure: ObjRef,
+    function: usize,
+    ip: usize,
+    // Stack slot of the callee, its arguments and locals follow.
+    base: usize,
+}
+
+// Executes a compiled `Program` on a value stack. Runtime errors read the same
+// as the ones from the tree-walking `Interpreter`.
+pub struct Vm {
+    program: Rc<Program>,
+    // The constant pool of every function, with strings already on the heap.
+    constants: Vec<Vec<Value>>,
+    heap: Heap,
+    stack: Vec<Value>,
+    frames: Vec<CallFrame>,
+    globals: HashMap<Symbol, Value>,
+    // Upvalues still pointing into the stack, ordered by slot.
+    open_upvalues: Vec<ObjRef>,
+    rng: Rng,
+}
+
+impl Vm {
+    pub fn new(program: Program) -> Vm {
+        Vm::with_gc(program, GcConfig::default())
+    }
+
+    pub fn with_gc(program: Program, config: GcConfig) -> Vm {
+        let mut heap = Heap::new(config);
+
+        let constants = program
+            .functions
+            .iter()
+            .map(|function| {
+                function
+                    .chunk
+                    .constants
+                    .iter()
+                    .map(|constant| match constant {
+                        LiteralValue::String(string) => Value::Obj(heap.alloc(Obj::String(string.clone()))),
+                        LiteralValue::Number(number) => Value::Number(*number),
+                        LiteralValue::Bool(boolean) => Value::Bool(*boolean),
+                        _ => Value::Nil,
+                    })
+                    .collect()
+            })
+            .collect();
+
+        let mut globals = HashMap::new();
+        for native in native::library::<Vm>() {
+            globals.insert(Symbol::intern(native.name), Value::Obj(heap.alloc(Obj::Native(native))));
+        }
+        for (name, value) in math::CONSTANTS {
+            globals.insert(Symbol::intern(name), Value::Number(value));
+        }
+
+        Vm {
+            program: Rc::new(program),
+            constants,
+            heap,
+            stack: Vec::new(),
+            frames: Vec::new(),
+            globals,
+            open_upvalues: Vec::new(),
+            rng: Rng::default(),
+        }
+    }
+
+    pub fn gc_stats(&self) -> GcStats {
+        self.heap.stats()
+    }
+
+    pub fn run(&mut self) -> Result<(), RuntimeError> {
+        let script = self.heap.alloc(Obj::Closure(Closure {
+            function: 0,
+            upvalues: Vec::new(),
+        }));
+        self.stack.push(Value::Obj(script));
+
+        let frame = CallFrame {
+            closure: script,
+            function: 0,
+            ip: 0,
+            base: 0,
+        };
+
+        let result = self.execute(frame);
+        if result.is_err() {
+            self.stack.clear();
+            self.frames.clear();
+            self.open_upvalues.clear();
+        }
+        result
+    }
+
+    // `frame` is the running function, `self.frames` only holds its callers.
+    fn execute(&mut self, mut frame: CallFrame) -> Result<(), RuntimeError> {
+        let program = Rc::clone(&self.program);
+
+        loop {
+            // Between instructions every live value is on the stack, in a
+            // frame, a global or an upvalue, so collecting here is safe.
+            if self.heap.should_collect() {
+                self.collect_garbage(frame.closure);
+            }
+
+            let chunk = &program.functions[frame.function].chunk;
+            let start = frame.ip;
+            let op = OpCode::from_byte(chunk.code[start]).expect("valid opcode");
+            frame.ip += 1;
+
+            match op {
+                OpCode::Constant => {
+                    let constant = Vm::read_u16(chunk, &mut frame);
+                    self.stack.push(self.constants[frame.function][constant]);
+                }
+                OpCode::Nil => self.stack.push(Value::Nil),
+                OpCode::True => self.stack.push(Value::Bool(true)),
+                OpCode::False => self.stack.push(Value::Bool(false)),
+                OpCode::Pop => {
+                    self.pop();
+                }
+                OpCode::GetLocal => {
+                    let slot = Vm::read_byte(chunk, &mut frame);
+                    self.stack.push(self.stack[frame.base + slot]);
+                }
+                OpCode::SetLocal => {
+                    let slot = Vm::read_byte(chunk, &mut frame);
+                    self.stack[frame.base + slot] = self.peek(0);
+                }
+                OpCode::GetGlobal => {
+                    let name = Vm::read_name(chunk, &mut frame);
+                    match self.globals.get(name) {
+                        Some(value) => self.stack.push(*value),
+                        None => return Err(Vm::error(chunk, start, format!("Undefined variable '{}'.", name))),
+                    }
+                }
+                OpCode::DefineGlobal => {
+                    let name = Vm::read_name(chunk, &mut frame);
+                    let value = self.pop();
+                    self.globals.insert(name.clone(), value);
+                }
+                OpCode::SetGlobal => {
+                    let name = Vm::read_name(chunk, &mut frame);
+                    let value = self.peek(0);
+                    match self.globals.get_mut(name) {
+                        Some(global) => *global = value,
+                        None => return Err(Vm::error(chunk, start, format!("Variable '{}' not found.", name))),
+                    }
+                }
+                OpCode::GetUpvalue => {
+                    let index = Vm::read_byte(chunk, &mut frame);
+                    let upvalue = self.upvalue(frame.closure, index);
+                    let value = match self.heap.get(upvalue) {
+                        Obj::Upvalue(Upvalue::Open(slot)) => self.stack.get(*slot).copied(),
+                        Obj::Upvalue(Upvalue::Closed(value)) => Some(*value),
+                        _ => unreachable!("closures only capture upvalues"),
+                    };
+                    let value = value.ok_or_else(|| Vm::error(chunk, start, CORRUPT.to_string()))?;
+                    self.stack.push(value);
+                }
+                OpCode::SetUpvalue => {
+                    let index = Vm::read_byte(chunk, &mut frame);
+                    let upvalue = self.upvalue(frame.closure, index);
+                    let value = self.peek(0);
+                    match self.heap.get_mut(upvalue) {
+                        Obj::Upvalue(Upvalue::Open(slot)) => {
+                            let slot = *slot;
+                            match self.stack.get_mut(slot) {
+                                Some(variable) => *variable = value,
+                                None => return Err(Vm::error(chunk, start, CORRUPT.to_string())),
+                            }
+                        }
+                        Obj::Upvalue(Upvalue::Closed(closed)) => *closed = value,
+                        _ => unreachable!("closures only capture upvalues"),
+                    }
+                }
+                OpCode::GetProperty => {
+                    let name = Vm::read_name(chunk, &mut frame);
+                    let receiver = self.peek(0);
+                    let value = self.property(receiver, name).map_err(|message| Vm::error(chunk, start, message))?;
+
+                    self.pop();
+                    self.stack.push(value);
+                }
+                OpCode::SetProperty => {
+                    let name = Vm::read_name(chunk, &mut frame);
+                    let value = self.peek(0);
+
+                    match self.peek(1) {
+                        Value::Obj(obj) => match self.heap.get_mut(obj) {
+                            Obj::Instance(instance) => {
+                                instance.fields.insert(name.clone(), value);
+                            }
+                            _ => return Err(Vm::error(chunk, start, "Only instances have fields.".to_string())),
+                        },
+                        _ => return Err(Vm::error(chunk, start, "Only instances have fields.".to_string())),
+                    }
+
+                    self.pop();
+                    self.pop();
+                    self.stack.push(value);
+                }
+                OpCode::GetSuper => {
+                    let name = Vm::read_name(chunk, &mut frame);
+                    let superclass = match self.pop() {
+                        Value::Obj(obj) => obj,
+                        _ => return Err(Vm::error(chunk, start, "Superclass must be a class.".to_string())),
+                    };
+                    let receiver = self.pop();
+
+                    match self.bind_method(superclass, receiver, name) {
+                        Some(method) => self.stack.push(method),
+                        None => return Err(Vm::error(chunk, start, format!("Undefined property '{}'.", name))),
+                    }
+                }
+                OpCode::Equal => {
+                    let right = self.pop();
+                    let left = self.pop();
+                    self.stack.push(Value::Bool(self.values_equal(left, right)));
+                }
+                OpCode::NotEqual => {
+                    let right = self.pop();
+                    let left = self.pop();
+                    self.stack.push(Value::Bool(!self.values_equal(left, right)));
+                }
+                OpCode::Greater
+                | OpCode::GreaterEqual
+                | OpCode::Less
+                | OpCode::LessEqual
+                | OpCode::Subtract
+                | OpCode::Multiply
+                | OpCode::Divide => {
+                    let (left, right) = match (self.peek(1), self.peek(0)) {
+                        (Value::Number(left), Value::Number(right)) => (left, right),
+                        _ => return Err(Vm::error(chunk, start, "Invalid operator".to_string())),
+                    };
+
+                    let result = match op {
+                        OpCode::Greater => Value::Bool(left > right),
+                        OpCode::GreaterEqual => Value::Bool(left >= right),
+                        OpCode::Less => Value::Bool(left < right),
+                        OpCode::LessEqual => Value::Bool(left <= right),
+                        OpCode::Subtract => Value::Number(left - right),
+                        OpCode::Multiply => Value::Number(left * right),
+                        _ => Value::Number(left / right),
+                    };
+
+                    self.pop();
+                    self.pop();
+                    self.stack.push(result);
+                }
+                OpCode::Interpolate => {
+                    let count = Vm::read_byte(chunk, &mut frame);
+                    let parts = self.stack.split_off(self.stack.len() - count);
+
+                    let string: String = parts.iter().map(|part| self.stringify(*part)).collect();
+                    let string = self.heap.alloc(Obj::String(Symbol::from(string)));
+                    self.stack.push(Value::Obj(string));
+                }
+                OpCode::BuildList => {
+                    let count = Vm::read_u16(chunk, &mut frame);
+                    let elements = self.stack.split_off(self.stack.len() - count);
+
+                    let list = self.heap.alloc(Obj::List(elements));
+                    self.stack.push(Value::Obj(list));
+                }
+                OpCode::BuildMap => {
+                    let count = Vm::read_u16(chunk, &mut frame);
+                    let pairs = self.stack.split_off(self.stack.len() - count * 2);
+
+                    let mut entries = LoxMap::new();
+                    for pair in pairs.chunks(2) {
+                        let key = self.map_key(pair[0]).map_err(|message| Vm::error(chunk, start, message))?;
+                        entries.insert(key, pair[1]);
+                    }
+
+                    let map = self.heap.alloc(Obj::Map(entries));
+                    self.stack.push(Value::Obj(map));
+                }
+                OpCode::GetIndex => {
+                    let index = self.pop();
+                    let object = self.pop();
+
+                    let value = self.get_index(object, index).map_err(|message| Vm::error(chunk, start, message))?;
+                    self.stack.push(value);
+                }
+                OpCode::SetIndex => {
+                    let value = self.pop();
+                    let index = self.pop();
+                    let object = self.pop();
+
+                    self.set_index(object, index, value).map_err(|message| Vm::error(chunk, start, message))?;
+                    self.stack.push(value);
+                }
+                OpCode::Add => {
+                    let result = match (self.peek(1), self.peek(0)) {
+                        (Value::Number(left), Value::Number(right)) => Value::Number(left + right),
+                        (Value::Obj(left), Value::Obj(right)) => match (self.heap.get(left), self.heap.get(right)) {
+                            (Obj::String(left), Obj::String(right)) => {
+                                let concatenated = format!("{}{}", left, right);
+                                Value::Obj(self.heap.alloc(Obj::String(Symbol::from(concatenated))))
+                            }
+                            _ => return Err(Vm::error(chunk, start, "Invalid operator".to_string())),
+                        },
+                        _ => return Err(Vm::error(chunk, start, "Invalid operator".to_string())),
+                    };
+
+                    self.pop();
+                    self.pop();
+                    self.stack.push(result);
+                }
+                OpCode::Not => {
+                    let value = self.pop();
+                    self.stack.push(Value::Bool(Vm::is_falsey(value)));
+                }
+                OpCode::Negate => match self.peek(0) {
+                    Value::Number(number) => {
+                        self.pop();
+                        self.stack.push(Value::Number(-number));
+                    }
+                    _ => return Err(Vm::error(chunk, start, "Tried negating a non number".to_string())),
+                },
+                OpCode::Print => {
+                    let value = self.pop();
+                    println!("{}", self.stringify(value));
+                }
+                OpCode::Jump => {
+                    let offset = Vm::read_u16(chunk, &mut frame);
+                    frame.ip += offset;
+                }
+                OpCode::JumpIfFalse => {
+                    let offset = Vm::read_u16(chunk, &mut frame);
+                    if Vm::is_falsey(self.peek(0)) {
+                        frame.ip += offset;
+                    }
+                }
+                OpCode::Loop => {
+                    let offset = Vm::read_u16(chunk, &mut frame);
+                    frame.ip -= offset;
+                }
+                OpCode::Call => {
+                    let arg_count = Vm::read_byte(chunk, &mut frame);
+                    let callee = self.peek(arg_count);
+
+                    if let Some(callee_frame) = self.call_value(callee, arg_count).map_err(|message| Vm::error(chunk, start, message))? {
+                        self.frames.push(frame);
+                        frame = callee_frame;
+                    }
+                }
+                OpCode::Invoke => {
+                    let name = Vm::read_name(chunk, &mut frame);
+                    let arg_count = Vm::read_byte(chunk, &mut frame);
+                    let receiver = self.peek(arg_count);
+                    let base = self.stack.len() - arg_count - 1;
+
+                    match receiver {
+                        Value::Obj(list) if matches!(self.heap.get(list), Obj::List(_)) => {
+                            let result = self
+                                .call_list_method(list, name, arg_count)
+                                .map_err(|message| Vm::error(chunk, start, message))?;
+                            self.stack.truncate(base);
+                            self.stack.push(result);
+                        }
+                        Value::Obj(map) if matches!(self.heap.get(map), Obj::Map(_)) => {
+                            let result = self
+                                .call_map_method(map, name, arg_count)
+                                .map_err(|message| Vm::error(chunk, start, message))?;
+                            self.stack.truncate(base);
+                            self.stack.push(result);
+                        }
+                        Value::Obj(string) if matches!(self.heap.get(string), Obj::String(_)) => {
+                            let result = self
+                                .call_string_method(name, arg_count)
+                                .map_err(|message| Vm::error(chunk, start, message))?;
+                            self.stack.truncate(base);
+                            self.stack.push(result);
+                        }
+                        _ => {
+                            let callee = self.property(receiver, name).map_err(|message| Vm::error(chunk, start, message))?;
+                            self.stack[base] = callee;
+
+                            if let Some(callee_frame) = self.call_value(callee, arg_count).map_err(|message| Vm::error(chunk, start, message))? {
+                                self.frames.push(frame);
+                                frame = callee_frame;
+                            }
+                        }
+                    }
+                }
+                OpCode::Closure => {
+                    let function = Vm::read_u16(chunk, &mut frame);
+
+                    let mut upvalues = Vec::with_capacity(program.functions[function].upvalues);
+                    for _ in 0..program.functions[function].upvalues {
+                        let is_local = Vm::read_byte(chunk, &mut frame) == 1;
+                        let index = Vm::read_byte(chunk, &mut frame);
+
+                        upvalues.push(if is_local {
+                            self.capture_upvalue(frame.base + index)
+                        } else {
+                            self.upvalue(frame.closure, index)
+                        });
+                    }
+
+                    let closure = self.heap.alloc(Obj::Closure(Closure { function, upvalues }));
+                    self.stack.push(Value::Obj(closure));
+                }
+                OpCode::CloseUpvalue => {
+                    self.close_upvalues(self.stack.len() - 1).map_err(|message| Vm::error(chunk, start, message))?;
+                    self.pop();
+                }
+                OpCode::Return => {
+                    let result = self.pop();
+                    self.close_upvalues(frame.base).map_err(|message| Vm::error(chunk, start, message))?;
+                    self.stack.truncate(frame.base);
+
+                    match self.frames.pop() {
+                        Some(caller) => {
+                            self.stack.push(result);
+                            frame = caller;
+                        }
+                        None => return Ok(()),
+                    }
+                }
+                OpCode::Class => {
+                    let name = Vm::read_name(chunk, &mut frame);
+                    let class = self.heap.alloc(Obj::Class(Class {
+                        name: name.to_string(),
+                        methods: HashMap::new(),
+                    }));
+                    self.stack.push(Value::Obj(class));
+                }
+                OpCode::Inherit => {
+                    // Methods are copied down so lookups never walk the hierarchy.
+                    let methods = match self.peek(1) {
+                        Value::Obj(obj) => match self.heap.get(obj) {
+                            Obj::Class(superclass) => superclass.methods.clone(),
+                            _ => return Err(Vm::error(chunk, start, "Superclass must be a class.".to_string())),
+                        },
+                        _ => return Err(Vm::error(chunk, start, "Superclass must be a class.".to_string())),
+                    };
+
+                    if let Value::Obj(subclass) = self.pop() {
+                        if let Obj::Class(subclass) = self.heap.get_mut(subclass) {
+                            subclass.methods.extend(methods);
+                        }
+                    }
+                }
+                OpCode::Method => {
+                    let name = Vm::read_name(chunk, &mut frame);
+                    let method = self.pop();
+
+                    if let (Value::Obj(class), Value::Obj(method)) = (self.peek(0), method) {
+                        if let Obj::Class(class) = self.heap.get_mut(class) {
+                            class.methods.insert(name.clone(), method);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    fn collect_garbage(&mut self, running: ObjRef) {
+        let mut tracer = Tracer::new();
+
+        running.trace(&mut tracer);
+        for frame in &self.frames {
+            frame.closure.trace(&mut tracer);
+        }
+        for value in self.stack.iter().chain(self.globals.values()).chain(self.constants.iter().flatten()) {
+            value.trace(&mut tracer);
+        }
+        for upvalue in &self.open_upvalues {
+            upvalue.trace(&mut tracer);
+        }
+
+        self.heap.collect(tracer);
+    }
+
+    // Returns the frame to switch to when the callee is a Lox function.
+    fn call_value(&mut self, callee: Value, arg_count: usize) -> Result<Option<CallFrame>, String> {
+        let base = self.stack.len() - arg_count - 1;
+
+        let obj = match callee {
+            Value::Obj(obj) => obj,
+            _ => return Err("Can only call functions and classes.".to_string()),
+        };
+
+        match self.heap.get(obj) {
+            Obj::Closure(_) => self.call(obj, arg_count).map(Some),
+            Obj::BoundMethod(bound) => {
+                let method = bound.method;
+                self.stack[base] = bound.receiver;
+                self.call(method, arg_count).map(Some)
+            }
+            Obj::Class(class) => {
+                let initializer = class.methods.get(&Symbol::intern("init")).copied();
+                let instance = self.heap.alloc(Obj::Instance(Instance {
+                    class: obj,
+                    fields: HashMap::new(),
+                }));
+                self.stack[base] = Value::Obj(instance);
+
+                match initializer {
+                    Some(initializer) => self.call(initializer, arg_count).map(Some),
+                    None if arg_count != 0 => Err(format!("Expected 0 arguments but got {}.", arg_count)),
+                    None => Ok(None),
+                }
+            }
+            Obj::Native(native) => {
+                let native = *native;
+                if arg_count != native.arity {
+                    return Err(format!("Expected {} arguments but got {}.", native.arity, arg_count));
+                }
+
+                let arguments = self.stack.split_off(base + 1);
+                let result = native.call(self, &arguments).map_err(|err| err.to_string())?;
+                self.stack.truncate(base);
+                self.stack.push(result);
+                Ok(None)
+            }
+            _ => Err("Can only call functions and classes.".to_string()),
+        }
+    }
+
+    fn call(&mut self, closure: ObjRef, arg_count: usize) -> Result<CallFrame, String> {
+        let function = match self.heap.get(closure) {
+            Obj::Closure(closure) => closure.function,
+            // Only corrupt bytecode makes a method out of anything else.
+            _ => return Err("Can only call functions and classes.".to_string()),
+        };
+
+        let arity = self.program.functions[function].arity;
+        if arg_count != arity {
+            return Err(format!("Expected {} arguments but got {}.", arity, arg_count));
+        }
+        if self.frames.len() + 1 >= FRAMES_MAX {
+            return Err("Stack overflow.".to_string());
+        }
+
+        Ok(CallFrame {
+            closure,
+            function,
+            ip: 0,
+            base: self.stack.len() - arg_count - 1,
+        })
+    }
+
+    fn bind_method(&mut self, class: ObjRef, receiver: Value, name: &Symbol) -> Option<Value> {
+        let method = match self.heap.get(class) {
+            Obj::Class(class) => *class.methods.get(name)?,
+            _ => return None,
+        };
+
+        let bound = self.heap.alloc(Obj::BoundMethod(BoundMethod { receiver, method }));
+        Some(Value::Obj(bound))
+    }
+
+    fn upvalue(&self, closure: ObjRef, index: usize) -> ObjRef {
+        match self.heap.get(closure) {
+            Obj::Closure(closure) => closure.upvalues[index],
+            _ => unreachable!("frames always run closures"),
+        }
+    }
+
+    // Closures capturing the same variable share a single upvalue.
+    fn capture_upvalue(&mut self, slot: usize) -> ObjRef {
+        let position = self.open_upvalues.iter().position(|upvalue| match self.heap.get(*upvalue) {
+            Obj::Upvalue(Upvalue::Open(open)) => *open >= slot,
+            _ => false,
+        });
+
+        if let Some(position) = position {
+            let existing = self.open_upvalues[position];
+            if let Obj::Upvalue(Upvalue::Open(open)) = self.heap.get(existing) {
+                if *open == slot {
+                    return existing;
+                }
+            }
+        }
+
+        let upvalue = self.heap.alloc(Obj::Upvalue(Upvalue::Open(slot)));
+        self.open_upvalues.insert(position.unwrap_or(self.open_upvalues.len()), upvalue);
+        upvalue
+    }
+
+    // Moves every captured variable at or above `last` off the stack.
+    fn close_upvalues(&mut self, last: usize) -> Result<(), String> {
+        while let Some(&upvalue) = self.open_upvalues.last() {
+            let slot = match self.heap.get(upvalue) {
+                Obj::Upvalue(Upvalue::Open(slot)) if *slot >= last => *slot,
+                _ => break,
+            };
+
+            let value = *self.stack.get(slot).ok_or_else(|| CORRUPT.to_string())?;
+            *self.heap.get_mut(upvalue) = Obj::Upvalue(Upvalue::Closed(value));
+            self.open_upvalues.pop();
+        }
+
+        Ok(())
+    }
+
+    fn values_equal(&self, left: Value, right: Value) -> bool {
+        match (left, right) {
+            (Value::Nil, Value::Nil) => true,
+            (Value::Bool(left), Value::Bool(right)) => left == right,
+            (Value::Number(left), Value::Number(right)) => left == right,
+            // Strings are interned, so equal text compares as the same symbol.
+            (Value::Obj(left), Value::Obj(right)) => match (self.heap.get(left), self.heap.get(right)) {
+                (Obj::String(left), Obj::String(right)) => left == right,
+                _ => left == right,
+            },
+            _ => false,
+        }
+    }
+
+    fn is_falsey(value: Value) -> bool {
+        matches!(value, Value::Nil | Value::Bool(false))
+    }
+
+    // Fields shadow methods.
+    fn property(&mut self, receiver: Value, name: &Symbol) -> Result<Value, String> {
+        let (class, field) = match receiver {
+            Value::Obj(obj) => match self.heap.get(obj) {
+                Obj::Instance(instance) => (instance.class, instance.fields.get(name).copied()),
+                _ => return Err("Only instances have properties.".to_string()),
+            },
+            _ => return Err("Only instances have properties.".to_string()),
+        };
+
+        match field {
+            Some(value) => Ok(value),
+            None => self
+                .bind_method(class, receiver, name)
+                .ok_or_else(|| format!("Undefined property '{}'.", name)),
+        }
+    }
+
+    // The arguments are still on the stack above the list.
+    fn call_list_method(&mut self, list: ObjRef, name: &Symbol, arg_count: usize) -> Result<Value, String> {
+        let method = ListMethod::find(name).ok_or_else(|| format!("Undefined property '{}'.", name))?;
+        if arg_count != method.arity() {
+            return Err(format!("Expected {} arguments but got {}.", method.arity(), arg_count));
+        }
+
+        let arguments = self.stack[self.stack.len() - arg_count..].to_vec();
+        let elements = match self.heap.get_mut(list) {
+            Obj::List(elements) => elements,
+            _ => unreachable!("list methods are only called on lists"),
+        };
+
+        match method {
+            ListMethod::Push => {
+                elements.extend(arguments);
+                Ok(Value::Nil)
+            }
+        }
+    }
+
+    // The arguments are still on the stack above the map.
+    fn call_map_method(&mut self, map: ObjRef, name: &Symbol, arg_count: usize) -> Result<Value, String> {
+        let method = MapMethod::find(name).ok_or_else(|| format!("Undefined property '{}'.", name))?;
+        if arg_count != method.arity() {
+            return Err(format!("Expected {} arguments but got {}.", method.arity(), arg_count));
+        }
+
+        let entries = match self.heap.get(map) {
+            Obj::Map(entries) => entries,
+            _ => unreachable!("map methods are only called on maps"),
+        };
+
+        match method {
+            MapMethod::Keys => {
+                let keys: Vec<MapKey> = entries.keys().cloned().collect();
+                let keys = keys.iter().map(|key| self.key_value(key)).collect();
+                Ok(Value::Obj(self.heap.alloc(Obj::List(keys))))
+            }
+            MapMethod::Values => {
+                let values = entries.values().copied().collect();
+                Ok(Value::Obj(self.heap.alloc(Obj::List(values))))
+            }
+            MapMethod::Has => {
+                let key = self.map_key(self.peek(0))?;
+                Ok(Value::Bool(entries.contains_key(&key)))
+            }
+        }
+    }
+
+    // The string and its arguments are still on the stack.
+    fn call_string_method(&mut self, name: &Symbol, arg_count: usize) -> Result<Value, String> {
+        let method = string::method::<Vm>(name).ok_or_else(|| format!("Undefined property '{}'.", name))?;
+        if arg_count != method.arity {
+            return Err(format!("Expected {} arguments but got {}.", method.arity, arg_count));
+        }
+
+        let arguments = self.stack[self.stack.len() - arg_count - 1..].to_vec();
+        method.call(self, &arguments).map_err(|err| err.to_string())
+    }
+
+    fn get_index(&mut self, object: Value, index: Value) -> Result<Value, String> {
+        let c = match self.heap.get(Vm::indexable(object)?) {
+            Obj::List(elements) => return Ok(elements[Vm::list_index(index, elements.len())?]),
+            Obj::Map(entries) => {
+                let key = self.map_key(index)?;
+                return entries.get(&key).copied().ok_or_else(|| format!("Undefined key {}.", key));
+            }
+            Obj::String(string) => string::char_at(string, self.number(&index))?,
+            _ => return Err("Only lists, maps and strings can be indexed.".to_string()),
+        };
+
+        Ok(Value::Obj(self.heap.alloc(Obj::String(Symbol::from(c.to_string())))))
+    }
+
+    fn set_index(&mut self, object: Value, index: Value, value: Value) -> Result<(), String> {
+        let obj = Vm::indexable(object)?;
+        let key = match self.heap.get(obj) {
+            Obj::Map(_) => Some(self.map_key(index)?),
+            _ => None,
+        };
+
+        match (self.heap.get_mut(obj), key) {
+            (Obj::List(elements), _) => {
+                let index = Vm::list_index(index, elements.len())?;
+                elements[index] = value;
+            }
+            (Obj::Map(entries), Some(key)) => entries.insert(key, value),
+            (Obj::String(_), _) => return Err("Strings are immutable.".to_string()),
+            _ => return Err("Only lists, maps and strings can be indexed.".to_string()),
+        }
+        Ok(())
+    }
+
+    fn indexable(object: Value) -> Result<ObjRef, String> {
+        match object {
+            Value::Obj(obj) => Ok(obj),
+            _ => Err("Only lists, maps and strings can be indexed.".to_string()),
+        }
+    }
+
+    fn map_key(&self, value: Value) -> Result<MapKey, String> {
+        match value {
+            Value::Nil => Ok(MapKey::Nil),
+            Value::Bool(boolean) => Ok(MapKey::Bool(boolean)),
+            Value::Number(number) => MapKey::number(number),
+            Value::Obj(obj) => match self.heap.get(obj) {
+                Obj::String(string) => Ok(MapKey::String(string.clone())),
+                _ => Err(MapKey::invalid()),
+            },
+        }
+    }
+
+    fn key_value(&mut self, key: &MapKey) -> Value {
+        match key {
+            MapKey::Nil => Value::Nil,
+            MapKey::Bool(boolean) => Value::Bool(*boolean),
+            MapKey::Number(bits) => Value::Number(f64::from_bits(*bits)),
+            MapKey::String(string) => Value::Obj(self.heap.alloc(Obj::String(string.clone()))),
+        }
+    }
+
+    fn list_index(index: Value, len: usize) -> Result<usize, String> {
+        match index {
+            Value::Number(number) if number.fract() == 0.0 => {
+                if number >= 0.0 && (number as usize) < len {
+                    Ok(number as usize)
+                } else {
+                    Err(format!("List index {} out of bounds for length {}.", number, len))
+                }
+            }
+            _ => Err("List index must be an integer.".to_string()),
+        }
+    }
+
+    // Formats values exactly like `print` in the tree-walker does.
+    fn stringify(&self, value: Value) -> String {
+        self.stringify_nested(value, &mut Vec::new())
+    }
+
+    // Mirrors `Interpreter::stringify`.
+    fn stringify_nested(&self, value: Value, open: &mut Vec<ObjRef>) -> String {
+        let obj = match value {
+            Value::Nil => return Utils::print_literal(&LiteralValue::Nil),
+            Value::Bool(boolean) => return Utils::print_literal(&LiteralValue::Bool(boolean)),
+            Value::Number(number) => return Utils::print_literal(&LiteralValue::Number(number)),
+            Value::Obj(obj) => obj,
+        };
+
+        match self.heap.get(obj) {
+            Obj::String(string) => string.to_string(),
+            Obj::Closure(closure) => format!("<fn {}>", self.program.functions[closure.function].name),
+            Obj::BoundMethod(bound) => self.stringify_nested(Value::Obj(bound.method), open),
+            Obj::Class(class) => class.name.clone(),
+            Obj::Instance(instance) => format!("{} instance", self.stringify(Value::Obj(instance.class))),
+            Obj::Native(native) => native.to_string(),
+            Obj::Upvalue(_) => "upvalue".to_string(),
+            Obj::List(_) if open.contains(&obj) => "[...]".to_string(),
+            Obj::List(elements) => {
+                open.push(obj);
+                let elements: Vec<String> = elements
+                    .iter()
+                    .map(|element| self.stringify_element(*element, open))
+                    .collect();
+                open.pop();
+
+                format!("[{}]", elements.join(", "))
+            }
+            Obj::Map(_) if open.contains(&obj) => "{...}".to_string(),
+            Obj::Map(entries) => {
+                open.push(obj);
+                let entries: Vec<String> = entries
+                    .iter()
+                    .map(|(key, value)| format!("{}: {}", key, self.stringify_element(*value, open)))
+                    .collect();
+                open.pop();
+
+                format!("{{{}}}", entries.join(", "))
+            }
+        }
+    }
+
+    fn stringify_element(&self, element: Value, open: &mut Vec<ObjRef>) -> String {
+        match element {
+            Value::Obj(string) if matches!(self.heap.get(string), Obj::String(_)) => {
+                format!("{:?}", self.stringify_nested(element, open))
+            }
+            element => self.stringify_nested(element, open),
+        }
+    }
+
+    fn pop(&mut self) -> Value {
+        self.stack.pop().expect("stack underflow")
+    }
+
+    fn peek(&self, distance: usize) -> Value {
+        self.stack[self.stack.len() - 1 - distance]
+    }
+
+    fn read_byte(chunk: &Chunk, frame: &mut CallFrame) -> usize {
+        let byte = chunk.code[frame.ip];
+        frame.ip += 1;
+        byte as usize
+    }
+
+    fn read_u16(chunk: &Chunk, frame: &mut CallFrame) -> usize {
+        let value = chunk.read_u16(frame.ip);
+        frame.ip += 2;
+        value as usize
+    }
+
+    fn read_name<'a>(chunk: &'a Chunk, frame: &mut CallFrame) -> &'a Symbol {
+        match &chunk.constants[Vm::read_u16(chunk, frame)] {
+            LiteralValue::String(name) => name,
+            _ => unreachable!("names are string constants"),
+        }
+    }
+
+    fn error(chunk: &Chunk, offset: usize, message: String) -> RuntimeError {
+        RuntimeError::at_line(chunk.lines[offset], message)
+    }
+}
+
+impl Runtime for Vm {
+    type Value = Value;
+
+    fn value_type(&self, value: &Value) -> ValueType {
+        let obj = match value {
+            Value::Nil => return ValueType::Nil,
+            Value::Bool(_) => return ValueType::Bool,
+            Value::Number(_) => return ValueType::Number,
+            Value::Obj(obj) => *obj,
+        };
+
+        match self.heap.get(obj) {
+            Obj::String(_) => ValueType::String,
+            Obj::List(_) => ValueType::List,
+            Obj::Map(_) => ValueType::Map,
+            Obj::Closure(_) | Obj::BoundMethod(_) | Obj::Native(_) => ValueType::Function,
+            Obj::Class(_) => ValueType::Class,
+            Obj::Instance(_) => ValueType::Instance,
+            Obj::Upvalue(_) => unreachable!("upvalues are never values"),
+        }
+    }
+
+    fn number(&self, value: &Value) -> Option<f64> {
+        match value {
+            Value::Number(number) => Some(*number),
+            _ => None,
+        }
+    }
+
+    fn string<'a>(&'a self, value: &'a Value) -> Option<&'a str> {
+        match value {
+            Value::Obj(obj) => match self.heap.get(*obj) {
+                Obj::String(string) => Some(string.as_str()),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    fn list<'a>(&'a self, value: &'a Value) -> Option<&'a [Value]> {
+        match value {
+            Value::Obj(obj) => match self.heap.get(*obj) {
+                Obj::List(elements) => Some(elements),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    fn map_len(&self, value: &Value) -> Option<usize> {
+        match value {
+            Value::Obj(obj) => match self.heap.get(*obj) {
+                Obj::Map(entries) => Some(entries.len()),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    fn stringify(&self, value: &Value) -> String {
+        Vm::stringify(self, *value)
+    }
+
+    fn literal(&mut self, value: LiteralValue) -> Value {
+        match value {
+            LiteralValue::Bool(boolean) => Value::Bool(boolean),
+            LiteralValue::Number(number) => Value::Number(number),
+            LiteralValue::String(string) => Value::Obj(self.heap.alloc(Obj::String(string))),
+            _ => Value::Nil,
+        }
+    }
+
+    fn alloc_list(&mut self, elements: Vec<Value>) -> Value {
+        Value::Obj(self.heap.alloc(Obj::List(elements)))
+    }
+
+    fn rng(&mut self) -> &mut Rng {
+        &mut self.rng
+    }
+}
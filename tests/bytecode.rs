@@ -0,0 +1,186 @@
+use std::fs;
+use std::path::PathBuf;
+use std::process::{Command, Output};
+use codecrafters_interpreter::bytecode::{self, MAGIC, VERSION};
+use codecrafters_interpreter::chunk::{Chunk, OpCode, Program, Prototype};
+use codecrafters_interpreter::compiler::Compiler;
+use codecrafters_interpreter::error::BytecodeError;
+use codecrafters_interpreter::parser::Parser;
+use codecrafters_interpreter::scanner::Scanner;
+
+const PROGRAM: &str = r#"
+fun counter() {
+  var count = 0;
+  fun increment() {
+    count = count + 1;
+    return count;
+  }
+  return increment;
+}
+
+class Greeter {
+  init(name) { this.name = name; }
+  greet() { return "hello ${this.name}"; }
+}
+
+var next = counter();
+next();
+print next();
+print Greeter("lox").greet();
+print [1, 2, 3][1] + {"a": 4}["a"];
+var i = 0;
+while (i < 3) { print i; i = i + 1; }
+"#;
+
+fn compile(source: &str) -> Program {
+    let (statements, errors) = Parser::new(Scanner::new(source.to_string())).parse();
+    assert!(errors.is_empty(), "{:?}", errors);
+    Compiler::new().compile(&statements).expect("program compiles")
+}
+
+fn temp_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("lox-bytecode-{}-{}", std::process::id(), name))
+}
+
+fn run(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_codecrafters-interpreter"))
+        .args(args)
+        .output()
+        .expect("interpreter runs")
+}
+
+// A single function for the top-level script.
+fn script(code: Vec<u8>) -> Program {
+    let lines = vec![1; code.len()];
+    Program {
+        functions: vec![Prototype {
+            name: "script".to_string(),
+            arity: 0,
+            upvalues: 0,
+            chunk: Chunk {
+                code,
+                lines,
+                constants: Vec::new(),
+            },
+        }],
+    }
+}
+
+#[test]
+fn decoding_an_encoded_program_gives_it_back() {
+    let encoded = bytecode::encode(&compile(PROGRAM));
+    let decoded = bytecode::decode(&encoded).expect("encoded program decodes");
+    assert_eq!(bytecode::encode(&decoded), encoded);
+}
+
+#[test]
+fn compiled_program_runs_like_its_source() {
+    let source = temp_path("round-trip.lox");
+    let compiled = temp_path("round-trip.loxc");
+    fs::write(&source, PROGRAM).unwrap();
+
+    let compile = run(&["compile", source.to_str().unwrap(), "-o", compiled.to_str().unwrap()]);
+    assert!(compile.status.success(), "{}", String::from_utf8_lossy(&compile.stderr));
+
+    let from_source = run(&["run", source.to_str().unwrap(), "--backend=vm"]);
+    let from_bytecode = run(&["run", compiled.to_str().unwrap()]);
+    fs::remove_file(&source).unwrap();
+    fs::remove_file(&compiled).unwrap();
+
+    assert!(from_source.status.success());
+    assert_eq!(from_bytecode.status.code(), from_source.status.code());
+    assert_eq!(String::from_utf8_lossy(&from_bytecode.stdout), String::from_utf8_lossy(&from_source.stdout));
+}
+
+#[test]
+fn compiled_program_refuses_the_tree_walker() {
+    let source = temp_path("tree.lox");
+    let compiled = temp_path("tree.loxc");
+    fs::write(&source, PROGRAM).unwrap();
+
+    run(&["compile", source.to_str().unwrap(), "-o", compiled.to_str().unwrap()]);
+    let tree = run(&["run", compiled.to_str().unwrap(), "--backend=tree"]);
+    fs::remove_file(&source).unwrap();
+    fs::remove_file(&compiled).unwrap();
+
+    assert_eq!(tree.status.code(), Some(64));
+    assert!(tree.stdout.is_empty());
+}
+
+#[test]
+fn compiled_program_disassembles_like_its_source() {
+    let source = temp_path("disassemble.lox");
+    let compiled = temp_path("disassemble.loxc");
+    fs::write(&source, PROGRAM).unwrap();
+
+    run(&["compile", source.to_str().unwrap(), "-o", compiled.to_str().unwrap()]);
+    let from_source = run(&["disassemble", source.to_str().unwrap()]);
+    let from_bytecode = run(&["disassemble", compiled.to_str().unwrap()]);
+    fs::remove_file(&source).unwrap();
+    fs::remove_file(&compiled).unwrap();
+
+    assert!(from_bytecode.status.success(), "{}", String::from_utf8_lossy(&from_bytecode.stderr));
+    assert_eq!(String::from_utf8_lossy(&from_bytecode.stdout), String::from_utf8_lossy(&from_source.stdout));
+}
+
+#[test]
+fn every_truncation_is_rejected() {
+    let encoded = bytecode::encode(&compile(PROGRAM));
+
+    for len in MAGIC.len()..encoded.len() {
+        assert!(
+            matches!(bytecode::decode(&encoded[..len]), Err(BytecodeError::Truncated)),
+            "{} of {} bytes decoded",
+            len,
+            encoded.len()
+        );
+    }
+}
+
+#[test]
+fn other_versions_are_rejected() {
+    let mut encoded = bytecode::encode(&compile(PROGRAM));
+    encoded[MAGIC.len()..MAGIC.len() + 2].copy_from_slice(&(VERSION + 1).to_le_bytes());
+
+    match bytecode::decode(&encoded) {
+        Err(BytecodeError::VersionMismatch { found, expected }) => {
+            assert_eq!(found, VERSION + 1);
+            assert_eq!(expected, VERSION);
+        }
+        result => panic!("expected a version mismatch, got {:?}", result.map(|_| ())),
+    }
+}
+
+#[test]
+fn code_reaching_outside_its_frame_is_rejected() {
+    let programs = [
+        // Only the script's closure is in slot 0.
+        vec![OpCode::GetLocal as u8, 1, OpCode::Return as u8],
+        vec![OpCode::Pop as u8, OpCode::Pop as u8, OpCode::Nil as u8, OpCode::Return as u8],
+        vec![OpCode::Add as u8, OpCode::Return as u8],
+        vec![OpCode::GetUpvalue as u8, 0, OpCode::Return as u8],
+        // Lands on the operand of the `GetLocal`.
+        vec![OpCode::Jump as u8, 0, 1, OpCode::GetLocal as u8, 0, OpCode::Return as u8],
+        // The loop pushes a value on every iteration.
+        vec![OpCode::Nil as u8, OpCode::Loop as u8, 0, 4, OpCode::Return as u8],
+    ];
+
+    for code in programs {
+        let encoded = bytecode::encode(&script(code.clone()));
+        assert!(
+            matches!(bytecode::decode(&encoded), Err(BytecodeError::Corrupt(_))),
+            "{:?} was accepted",
+            code
+        );
+    }
+}
+
+#[test]
+fn compiling_a_missing_file_fails_without_output() {
+    let source = temp_path("missing.lox");
+    let compiled = temp_path("missing.loxc");
+
+    let compile = run(&["compile", source.to_str().unwrap(), "-o", compiled.to_str().unwrap()]);
+    assert_eq!(compile.status.code(), Some(66));
+    assert!(!compiled.exists());
+}
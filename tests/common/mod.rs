@@ -0,0 +1,56 @@
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output, Stdio};
+
+pub const BACKENDS: [&str; 2] = ["tree", "vm"];
+
+// The `.lox` files in a directory under `tests`, in name order.
+pub fn scripts(dir: &str) -> Vec<PathBuf> {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests").join(dir);
+    let mut scripts: Vec<PathBuf> = fs::read_dir(&dir)
+        .unwrap_or_else(|err| panic!("can't list {}: {}", dir.display(), err))
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|extension| extension == "lox"))
+        .collect();
+    scripts.sort();
+    scripts
+}
+
+pub fn run(script: &Path, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_codecrafters-interpreter"))
+        .arg("run")
+        .arg(script)
+        .args(args)
+        .stdin(Stdio::null())
+        .output()
+        .expect("interpreter runs")
+}
+
+// What a run showed: the standard output and, when it failed, the exit code
+// and standard error.
+pub fn transcript(output: &Output) -> String {
+    let mut transcript = String::from_utf8_lossy(&output.stdout).into_owned();
+    if !output.status.success() {
+        let code = output.status.code().map_or("signal".to_string(), |code| code.to_string());
+        transcript.push_str(&format!("--- exit {} ---\n{}", code, String::from_utf8_lossy(&output.stderr)));
+    }
+    transcript
+}
+
+// Compares a transcript with the script's `.out` file. Setting UPDATE_GOLDEN
+// rewrites the file instead.
+pub fn check_golden(script: &Path, transcript: &str) -> Result<(), String> {
+    let golden = script.with_extension("out");
+    if env::var_os("UPDATE_GOLDEN").is_some() {
+        fs::write(&golden, transcript).unwrap();
+        return Ok(());
+    }
+
+    let expected = fs::read_to_string(&golden).map_err(|err| format!("can't read {}: {}", golden.display(), err))?;
+    if expected == transcript {
+        Ok(())
+    } else {
+        Err(format!("expected:\n{}\ngot:\n{}", expected, transcript))
+    }
+}
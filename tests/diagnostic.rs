@@ -0,0 +1,134 @@
+use codecrafters_interpreter::diagnostic::Diagnostic;
+use codecrafters_interpreter::error::{LoxError, RuntimeError};
+use codecrafters_interpreter::parser::Parser;
+use codecrafters_interpreter::scanner::Scanner;
+use codecrafters_interpreter::symbol::Symbol;
+use codecrafters_interpreter::token::{Position, Span, Token};
+use codecrafters_interpreter::token_type::TokenType;
+
+fn diagnostic(line: usize, column: Option<usize>, start: usize, end: usize) -> Diagnostic {
+    Diagnostic {
+        kind: "test",
+        message: "Something went wrong.".to_string(),
+        line,
+        column,
+        span: Span { start, end },
+        label: None,
+    }
+}
+
+// The first parse error in `source`, rendered without color.
+fn parse_error(source: &str) -> String {
+    let (_, errors) = Parser::new(Scanner::new(source.to_string())).parse();
+    Diagnostic::from(&LoxError::from(errors[0].clone())).render(source, "test.lox", false)
+}
+
+#[test]
+fn underlines_the_span_on_its_line() {
+    let source = "var a = 1;\nprint a + nil;";
+    assert_eq!(
+        diagnostic(2, Some(7), 17, 24).render(source, "test.lox", false),
+        "error[test]: Something went wrong.\n \
+         --> test.lox:2:7\n  \
+         |\n\
+         2 | print a + nil;\n  \
+         |       ^^^^^^^\n"
+    );
+}
+
+#[test]
+fn padding_keeps_tabs() {
+    let source = "\t\tprint nil;";
+    assert_eq!(
+        diagnostic(1, Some(9), 8, 11).render(source, "test.lox", false),
+        "error[test]: Something went wrong.\n \
+         --> test.lox:1:9\n  \
+         |\n\
+         1 | \t\tprint nil;\n  \
+         | \t\t      ^^^\n"
+    );
+}
+
+#[test]
+fn without_a_column_only_the_line_is_shown() {
+    let source = "print 1;\nprint -nil;";
+    assert_eq!(
+        diagnostic(2, None, 0, 0).render(source, "test.lox", false),
+        "error[test]: Something went wrong.\n \
+         --> test.lox:2\n  \
+         |\n\
+         2 | print -nil;\n"
+    );
+}
+
+#[test]
+fn span_over_several_lines_is_underlined_on_the_line_shown() {
+    // The span starts on line 1 but the error is reported on line 2.
+    let source = "var abcdef\nxy;";
+    assert_eq!(
+        diagnostic(2, Some(1), 4, 13).render(source, "test.lox", false),
+        "error[test]: Something went wrong.\n \
+         --> test.lox:2:1\n  \
+         |\n\
+         2 | xy;\n  \
+         | ^^\n"
+    );
+    // And the other way around, only the first line's part is underlined.
+    assert_eq!(
+        diagnostic(1, Some(5), 4, 13).render(source, "test.lox", false),
+        "error[test]: Something went wrong.\n \
+         --> test.lox:1:5\n  \
+         |\n\
+         1 | var abcdef\n  \
+         |     ^^^^^^\n"
+    );
+}
+
+#[test]
+fn error_at_end_of_file_points_past_the_last_token() {
+    assert_eq!(
+        parse_error("print 1"),
+        "error[parse]: Expect ';'\n \
+         --> test.lox:1:8\n  \
+         |\n\
+         1 | print 1\n  \
+         |        ^ unexpected end of file\n"
+    );
+}
+
+#[test]
+fn error_at_end_of_file_after_a_newline_is_on_the_empty_last_line() {
+    assert_eq!(
+        parse_error("print 1\n"),
+        "error[parse]: Expect ';'\n \
+         --> test.lox:2:1\n  \
+         |\n\
+         2 | \n  \
+         | ^ unexpected end of file\n"
+    );
+}
+
+#[test]
+fn runtime_error_at_end_of_file_keeps_its_column() {
+    let at = Position { line: 1, column: 8 };
+    let token = Token::new(TokenType::Eof, Symbol::intern(""), None, at, at, Span { start: 7, end: 7 });
+    let err = LoxError::from(RuntimeError::new(token, "Something went wrong.".to_string()));
+
+    assert_eq!(err.column(), Some(8));
+    assert_eq!(err.located(), "Something went wrong.\n[line 1:8]");
+}
+
+#[test]
+fn runtime_error_from_the_vm_has_only_a_line() {
+    let err = LoxError::from(RuntimeError::at_line(2, "Something went wrong.".to_string()));
+
+    assert_eq!(err.column(), None);
+    assert_eq!(err.located(), "Something went wrong.\n[line 2]");
+    assert_eq!(
+        Diagnostic::from(&err).render("print 1;\nprint -nil;", "test.lox", false),
+        "error[runtime]: Something went wrong.\n \
+         --> test.lox:2\n  \
+         |\n\
+         2 | print -nil;\n"
+    );
+}
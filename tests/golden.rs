@@ -0,0 +1,71 @@
+mod common;
+
+use std::fs::File;
+use std::path::Path;
+use std::process::{Command, Output};
+use common::BACKENDS;
+
+fn disassemble(script: &Path) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_codecrafters-interpreter"))
+        .arg("disassemble")
+        .arg(script)
+        .output()
+        .expect("interpreter runs")
+}
+
+// Runs the script the way `cat script | lox run -` would.
+fn run_stdin(script: &Path) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_codecrafters-interpreter"))
+        .args(["run", "-"])
+        .stdin(File::open(script).expect("script opens"))
+        .output()
+        .expect("interpreter runs")
+}
+
+// Every script under `tests/golden` has to print exactly its `.out` file on
+// both backends, which also keeps the backends in step with each other.
+#[test]
+fn scripts_match_their_golden_output() {
+    let mut failures = Vec::new();
+
+    for script in common::scripts("golden") {
+        for backend in BACKENDS {
+            let transcript = common::transcript(&common::run(&script, &[&format!("--backend={}", backend)]));
+            if let Err(diff) = common::check_golden(&script, &transcript) {
+                failures.push(format!("{} on the {} backend, {}", script.display(), backend, diff));
+            }
+        }
+    }
+
+    assert!(failures.is_empty(), "{}", failures.join("\n"));
+}
+
+// Piped programs are scanned as they are read, and have to run the same.
+#[test]
+fn scripts_on_standard_input_match_their_golden_output() {
+    let mut failures = Vec::new();
+
+    for script in common::scripts("golden") {
+        let transcript = common::transcript(&run_stdin(&script));
+        if let Err(diff) = common::check_golden(&script, &transcript) {
+            failures.push(format!("{} on standard input, {}", script.display(), diff));
+        }
+    }
+
+    assert!(failures.is_empty(), "{}", failures.join("\n"));
+}
+
+// The bytecode listing of every script under `tests/golden/disassemble`.
+#[test]
+fn disassembly_matches_its_golden_output() {
+    let mut failures = Vec::new();
+
+    for script in common::scripts("golden/disassemble") {
+        let transcript = common::transcript(&disassemble(&script));
+        if let Err(diff) = common::check_golden(&script, &transcript) {
+            failures.push(format!("{} disassembled, {}", script.display(), diff));
+        }
+    }
+
+    assert!(failures.is_empty(), "{}", failures.join("\n"));
+}
@@ -0,0 +1,69 @@
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+// Feeds `input` to the REPL line by line, returns what it printed to the
+// standard output and the standard error. The prompts show whether a line
+// finished the input (`> `) or left it open (`... `).
+fn session(input: &str) -> (String, String) {
+    let mut repl = Command::new(env!("CARGO_BIN_EXE_codecrafters-interpreter"))
+        .arg("repl")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("interpreter runs");
+    repl.stdin.take().unwrap().write_all(input.as_bytes()).unwrap();
+    let output = repl.wait_with_output().unwrap();
+
+    assert!(output.status.success());
+    (
+        String::from_utf8_lossy(&output.stdout).into_owned(),
+        String::from_utf8_lossy(&output.stderr).into_owned(),
+    )
+}
+
+#[test]
+fn unbalanced_brackets_continue_on_the_next_line() {
+    let (stdout, stderr) = session("var a = [1,\n(2 +\n3)];\n{\nprint a;\n}\n");
+    assert_eq!(stdout, "> ... ... > ... ... [1, 5]\n> \n");
+    assert_eq!(stderr, "");
+}
+
+#[test]
+fn unterminated_strings_and_comments_continue_on_the_next_line() {
+    let (stdout, stderr) = session("print \"one\ntwo\";\n/* open\n/* nested */ still\n*/ print 3;\n");
+    assert_eq!(stdout, "> ... one\ntwo\n> ... ... 3\n> \n");
+    assert_eq!(stderr, "");
+}
+
+#[test]
+fn only_errors_at_the_end_of_input_continue_it() {
+    let (stdout, stderr) = session("print 1\n;\nprint 1 +;\n");
+    assert_eq!(stdout, "> ... 1\n> > \n");
+    assert_eq!(stderr, "[line 1] Error at ';': Expect expression.\n");
+}
+
+#[test]
+fn bare_expressions_are_echoed() {
+    let (stdout, _) = session("1 + 2\n\"a\" + \"b\"\n");
+    assert_eq!(stdout, "> 3\n> ab\n> \n");
+}
+
+#[test]
+fn cancel_discards_the_unfinished_input() {
+    let (stdout, stderr) = session("print (1 +\n:cancel\nprint 2;\n:history\n");
+    assert_eq!(stdout, "> ... > 2\n>    1  print 2;\n> \n");
+    assert_eq!(stderr, "");
+}
+
+#[test]
+fn history_runs_an_input_again() {
+    let (stdout, _) = session("var a = 1;\na = a +\n1;\n:history 2\nprint a;\n:history 9\n");
+    assert_eq!(stdout, "> > ... > a = a +\n1;\n> 3\n> No input 9 in the history\n> \n");
+}
+
+#[test]
+fn env_lists_only_the_variables_defined_in_the_session() {
+    let (stdout, _) = session("var b = \"two\";\nvar a = 1;\n:env\n");
+    assert_eq!(stdout, "> > > a = 1\nb = two\n> \n");
+}
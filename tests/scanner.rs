@@ -0,0 +1,145 @@
+use std::io::{self, BufReader, Cursor, Read, Write};
+use std::process::{Command, Stdio};
+use codecrafters_interpreter::diagnostic::Diagnostic;
+use codecrafters_interpreter::error::{LoxError, ScanErrorKind};
+use codecrafters_interpreter::scanner::Scanner;
+
+fn error_kinds(source: &str) -> Vec<ScanErrorKind> {
+    let (_, errors) = Scanner::new(source.to_string()).scan_tokens();
+    errors.into_iter().map(|err| err.kind).collect()
+}
+
+#[test]
+fn unterminated_string_inside_interpolation_is_reported_once() {
+    assert_eq!(error_kinds(r#"print "${";"#), [ScanErrorKind::UnterminatedString]);
+    assert_eq!(error_kinds(r#"print "${"a" + "${";"#), [ScanErrorKind::UnterminatedString]);
+}
+
+#[test]
+fn unterminated_string_after_interpolation_is_reported_once() {
+    assert_eq!(error_kinds(r#"print "${1}"#), [ScanErrorKind::UnterminatedString]);
+}
+
+#[test]
+fn unclosed_interpolation_is_an_unterminated_string() {
+    assert_eq!(error_kinds(r#"print "${1"#), [ScanErrorKind::UnterminatedString]);
+}
+
+#[test]
+fn unterminated_nested_comment_is_reported_once_where_it_opens() {
+    let source = "print 1;\n  /* outer\n/* inner */\n/* still open\n";
+    let (tokens, errors) = Scanner::new(source.to_string()).scan_tokens();
+
+    assert_eq!(errors.len(), 1, "{:?}", errors);
+    assert_eq!(errors[0].kind, ScanErrorKind::UnterminatedComment);
+    assert_eq!((errors[0].line, errors[0].column), (2, 3));
+    // Every newline inside the comment is still counted.
+    assert_eq!(tokens.last().unwrap().line, 5);
+}
+
+#[test]
+fn lines_after_a_nested_comment_are_counted() {
+    let source = "/* one\n/* two\n*/ three\n*/ print\n1;";
+    let (tokens, errors) = Scanner::new(source.to_string()).scan_tokens();
+
+    assert!(errors.is_empty(), "{:?}", errors);
+    let lines: Vec<(&str, usize)> = tokens.iter().map(|token| (token.lexeme.as_str(), token.line)).collect();
+    assert_eq!(lines, [("print", 4), ("1", 5), (";", 5), ("", 5)]);
+}
+
+// How `--pretty-errors` shows the only error in `source`.
+fn rendered(source: &str) -> String {
+    let (_, errors) = Scanner::new(source.to_string()).scan_tokens();
+    assert_eq!(errors.len(), 1, "{:?}", errors);
+    Diagnostic::from(&LoxError::from(errors[0].clone())).render(source, "test.lox", false)
+}
+
+#[test]
+fn multi_line_unterminated_string_underlines_its_last_line() {
+    assert_eq!(
+        rendered("print \"line1\nline2 unterminated"),
+        "error[scan]: Unterminated string.\n \
+         --> test.lox:2:1\n  \
+         |\n\
+         2 | line2 unterminated\n  \
+         | ^^^^^^^^^^^^^^^^^^\n"
+    );
+}
+
+#[test]
+fn unterminated_string_ending_in_newlines_is_shown_on_its_last_text() {
+    assert_eq!(
+        rendered("print \"line1\nline2\n\n"),
+        "error[scan]: Unterminated string.\n \
+         --> test.lox:2:1\n  \
+         |\n\
+         2 | line2\n  \
+         | ^^^^^\n"
+    );
+}
+
+#[test]
+fn closed_interpolation_has_no_errors() {
+    assert_eq!(error_kinds(r#"print "a${"b${1}"}c";"#), []);
+}
+
+const SOURCE: &str = r#"var greeting = "héllo
+wörld";
+/* a block comment
+   /* nested */ over lines */
+print "${greeting} ${1 + "x${2.5}"}" + "\u{41}";
+"unterminated"#;
+
+// Fails the test if the scanner reads further than it has to.
+struct Untouchable;
+
+impl Read for Untouchable {
+    fn read(&mut self, _buf: &mut [u8]) -> io::Result<usize> {
+        panic!("read past the first line");
+    }
+}
+
+#[test]
+fn reading_incrementally_gives_the_same_tokens() {
+    let whole: Vec<_> = Scanner::new(SOURCE.to_string()).map(|result| format!("{:?}", result)).collect();
+    let streamed: Vec<_> = Scanner::from_reader(Cursor::new(SOURCE))
+        .map(|result| format!("{:?}", result))
+        .collect();
+    assert_eq!(streamed, whole);
+}
+
+#[test]
+fn tokens_come_out_before_the_next_line_is_read() {
+    let input = BufReader::new(Cursor::new("print 1;\n").chain(Untouchable));
+    let lexemes: Vec<_> = Scanner::from_reader(input)
+        .take(3)
+        .map(|result| result.unwrap().lexeme.to_string())
+        .collect();
+    assert_eq!(lexemes, ["print", "1", ";"]);
+}
+
+#[test]
+fn unreadable_input_is_an_error_after_the_tokens_before_it() {
+    let (tokens, errors) = Scanner::from_reader(Cursor::new(b"print 1;\n\xff\n".to_vec())).scan_tokens();
+    assert_eq!(tokens.len(), 4);
+    assert_eq!(errors.iter().map(|err| err.kind).collect::<Vec<_>>(), [ScanErrorKind::Unreadable]);
+    assert_eq!(errors[0].line, 2);
+}
+
+#[test]
+fn tokenize_prints_every_string_on_one_line() {
+    let mut tokenize = Command::new(env!("CARGO_BIN_EXE_codecrafters-interpreter"))
+        .args(["tokenize", "-"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .expect("interpreter runs");
+    tokenize.stdin.take().unwrap().write_all(br#"print "a\nb\t";"#).unwrap();
+    let output = tokenize.wait_with_output().unwrap();
+
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "PRINT print null\nSTRING \"a\\nb\\t\" a\\nb\\t\nSEMICOLON ; null\nEOF  null\n"
+    );
+}